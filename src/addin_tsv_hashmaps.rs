@@ -1,4 +1,7 @@
-use std::{collections::HashMap, fs::File};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+};
 
 use crate::tsv_types::*;
 
@@ -11,12 +14,18 @@ pub type VernacularCommonName = Vec<String>;
 
 pub fn vernacular_to_hashmap(
     tsv_iter: csv::DeserializeRecordsIter<'_, File, VernacularNameTSVRaw>,
+    taxon_ids: Option<&HashSet<String>>,
 ) -> HashMap<VernacularHashKey, VernacularCommonName> {
     let mut hashmap: HashMap<VernacularHashKey, VernacularCommonName> = HashMap::new();
     for tsv_reader_result in tsv_iter {
         let Ok(ok) = tsv_reader_result else {
             continue;
         };
+        if let Some(taxon_ids) = taxon_ids
+            && !taxon_ids.contains(&ok.dwc_taxon_id)
+        {
+            continue;
+        }
         let key = VernacularHashKey {
             language_code: ok.dcterms_language,
             taxon_id: ok.dwc_taxon_id,
@@ -31,26 +40,88 @@ pub fn vernacular_to_hashmap(
 
 pub fn species_profile_to_hashmap(
     tsv_iter: csv::DeserializeRecordsIter<'_, File, SpeciesProfileTSVRaw>,
+    taxon_ids: Option<&HashSet<String>>,
 ) -> HashMap<std::string::String, SpeciesProfileTSVRaw> {
     let mut hashmap: HashMap<String, SpeciesProfileTSVRaw> = HashMap::new();
     for tsv_reader_result in tsv_iter {
         let Ok(ok) = tsv_reader_result else {
             continue;
         };
-        hashmap.insert(ok.dwc_taxon_id.clone(), ok);
+        if let Some(taxon_ids) = taxon_ids
+            && !taxon_ids.contains(&ok.dwc_taxon_id)
+        {
+            continue;
+        }
+        // a taxon can have a profile row per source, a flag is true if any row says so
+        // (`Option<bool>` orders `None < Some(false) < Some(true)`)
+        hashmap
+            .entry(ok.dwc_taxon_id.clone())
+            .and_modify(|x| {
+                x.gbif_is_extinct = x.gbif_is_extinct.max(ok.gbif_is_extinct);
+                x.gbif_is_marine = x.gbif_is_marine.max(ok.gbif_is_marine);
+                x.gbif_is_freshwater = x.gbif_is_freshwater.max(ok.gbif_is_freshwater);
+                x.gbif_is_terrestrial = x.gbif_is_terrestrial.max(ok.gbif_is_terrestrial);
+            })
+            .or_insert(ok);
     }
     return hashmap;
 }
 
+// a taxon has a row per area it occurs in
 pub fn distribution_to_hashmap(
     tsv_iter: csv::DeserializeRecordsIter<'_, File, DistributionTSVRaw>,
-) -> HashMap<std::string::String, DistributionTSVRaw> {
-    let mut hashmap: HashMap<String, DistributionTSVRaw> = HashMap::new();
+    taxon_ids: Option<&HashSet<String>>,
+) -> HashMap<std::string::String, Vec<DistributionTSVRaw>> {
+    let mut hashmap: HashMap<String, Vec<DistributionTSVRaw>> = HashMap::new();
     for tsv_reader_result in tsv_iter {
         let Ok(ok) = tsv_reader_result else {
             continue;
         };
-        hashmap.insert(ok.dwc_taxon_id.clone(), ok);
+        if let Some(taxon_ids) = taxon_ids
+            && !taxon_ids.contains(&ok.dwc_taxon_id)
+        {
+            continue;
+        }
+        hashmap.entry(ok.dwc_taxon_id.clone()).or_default().push(ok);
     }
     return hashmap;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsv_parsing;
+
+    const FIXTURE_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn read_species_profiles(
+        taxon_ids: Option<&HashSet<String>>,
+    ) -> HashMap<String, SpeciesProfileTSVRaw> {
+        let mut species_profile_tsv_reader =
+            tsv_parsing::tsv_reader(&format!("{FIXTURE_DATA_DIR}/SpeciesProfile.tsv"));
+        return species_profile_to_hashmap(species_profile_tsv_reader.deserialize(), taxon_ids);
+    }
+
+    #[test]
+    fn merges_species_profile_rows() {
+        let species_profiles = read_species_profiles(None);
+        assert_eq!(species_profiles.len(), 3);
+        // one row says freshwater, the other terrestrial, and any row that sets a flag wins
+        let eoophyla_peribocalis = &species_profiles["7DKZ3"];
+        assert_eq!(eoophyla_peribocalis.gbif_is_freshwater, Some(true));
+        assert_eq!(eoophyla_peribocalis.gbif_is_terrestrial, Some(true));
+        assert_eq!(eoophyla_peribocalis.gbif_is_extinct, Some(false));
+        assert_eq!(eoophyla_peribocalis.gbif_is_marine, None);
+
+        let agrotis_ipsilon = &species_profiles["6XQTZ"];
+        assert_eq!(agrotis_ipsilon.gbif_is_freshwater, Some(false));
+        assert_eq!(agrotis_ipsilon.gbif_is_terrestrial, Some(true));
+    }
+
+    #[test]
+    fn skips_species_profiles_of_other_taxa() {
+        let taxon_ids = HashSet::from(["7DKZ3".to_string()]);
+        let species_profiles = read_species_profiles(Some(&taxon_ids));
+        assert_eq!(species_profiles.keys().collect::<Vec<&String>>(), ["7DKZ3"]);
+    }
+}
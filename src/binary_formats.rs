@@ -0,0 +1,51 @@
+use std::{
+    error::Error,
+    io::{BufReader, Read, Write},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+// structs are written as maps since the json types skip empty fields, which positional encodings can't represent
+
+/// Writes any of the outputs (`MothDataJson`, `MothSynonyms`, `ButterflyBlacklist`) as MessagePack.
+#[cfg(feature = "msgpack")]
+pub fn write_msgpack<T: Serialize, W: Write>(
+    mut writer: W,
+    value: &T,
+) -> Result<(), Box<dyn Error>> {
+    rmp_serde::encode::write_named(&mut writer, value)?;
+    writer.flush()?;
+    return Ok(());
+}
+
+/// Reads a MessagePack output, e.g. `read_msgpack::<MothDataJson, _>(File::open("moth_data.msgpack")?)`.
+#[cfg(feature = "msgpack")]
+pub fn read_msgpack<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, Box<dyn Error>> {
+    return Ok(rmp_serde::from_read(BufReader::new(reader))?);
+}
+
+/// Reads a zstd compressed MessagePack output such as `moth_data.msgpack.zst`.
+#[cfg(all(feature = "msgpack", feature = "zstd"))]
+pub fn read_msgpack_zstd<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, Box<dyn Error>> {
+    return read_msgpack(zstd::Decoder::new(reader)?);
+}
+
+/// Writes any of the outputs (`MothDataJson`, `MothSynonyms`, `ButterflyBlacklist`) as CBOR.
+#[cfg(feature = "cbor")]
+pub fn write_cbor<T: Serialize, W: Write>(mut writer: W, value: &T) -> Result<(), Box<dyn Error>> {
+    ciborium::into_writer(value, &mut writer)?;
+    writer.flush()?;
+    return Ok(());
+}
+
+/// Reads a CBOR output, e.g. `read_cbor::<MothDataJson, _>(File::open("moth_data.cbor")?)`.
+#[cfg(feature = "cbor")]
+pub fn read_cbor<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, Box<dyn Error>> {
+    return Ok(ciborium::from_reader(BufReader::new(reader))?);
+}
+
+/// Reads a zstd compressed CBOR output such as `moth_data.cbor.zst`.
+#[cfg(all(feature = "cbor", feature = "zstd"))]
+pub fn read_cbor_zstd<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, Box<dyn Error>> {
+    return read_cbor(zstd::Decoder::new(reader)?);
+}
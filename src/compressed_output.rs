@@ -0,0 +1,73 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+// 128 MiB window so repeated species blocks far apart in the json still match
+const LONG_DISTANCE_WINDOW_LOG: u32 = 27;
+
+pub struct ZstdOptions {
+    pub level: i32,
+    pub long_distance_matching: bool,
+    pub threads: u32,
+}
+
+impl Default for ZstdOptions {
+    fn default() -> ZstdOptions {
+        return ZstdOptions {
+            level: *zstd::compression_level_range().end(),
+            long_distance_matching: false,
+            threads: 0,
+        };
+    }
+}
+
+// writes every byte to both the plain file and the zstd encoder
+struct TeeWriter<'a> {
+    plain: BufWriter<File>,
+    compressed: zstd::Encoder<'a, BufWriter<File>>,
+}
+
+impl Write for TeeWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.plain.write_all(buf)?;
+        self.compressed.write_all(buf)?;
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.plain.flush()?;
+        return self.compressed.flush();
+    }
+}
+
+/// Runs `write` once, producing `output_file_path` and a zstd compressed copy at `output_file_path.zst` in the same pass.
+pub fn write_with_zstd_copy(
+    output_file_path: &str,
+    zstd_options: &ZstdOptions,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let output_file_path_zstd = output_file_path.to_owned() + ".zst";
+    let mut compressed = zstd::Encoder::new(
+        BufWriter::new(File::create(&output_file_path_zstd)?),
+        zstd_options.level,
+    )?;
+    if zstd_options.long_distance_matching {
+        compressed.long_distance_matching(true)?;
+        compressed.window_log(LONG_DISTANCE_WINDOW_LOG)?;
+    }
+    if zstd_options.threads > 0 {
+        compressed.multithread(zstd_options.threads)?;
+    }
+
+    let mut tee_writer = TeeWriter {
+        plain: BufWriter::new(File::create(output_file_path)?),
+        compressed,
+    };
+    write(&mut tee_writer)?;
+
+    tee_writer.plain.flush()?;
+    tee_writer.compressed.finish()?.flush()?;
+    return Ok(());
+}
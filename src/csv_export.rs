@@ -0,0 +1,118 @@
+use std::error::Error;
+
+use moth_filter::*;
+use serde::Serialize;
+
+const LIST_SEPARATOR: &str = "|";
+
+#[derive(Serialize)]
+struct MothDataRow<'a> {
+    catalogue_of_life_taxon_id: &'a str,
+    scientific_name: String,
+    superfamily: Option<&'a str>,
+    family: Option<&'a str>,
+    subfamily: Option<&'a str>,
+    tribe: Option<&'a str>,
+    subtribe: Option<&'a str>,
+    genus: &'a str,
+    epithet: &'a str,
+    generic_name: Option<&'a str>,
+    common_names: Option<String>,
+    synonyms_count: usize,
+    extinct: Option<bool>,
+    freshwater: Option<bool>,
+    marine: Option<bool>,
+    terrestrial: Option<bool>,
+    habitat: Option<Habitat>,
+    locality: Option<&'a str>,
+    localities: Option<String>,
+    country_codes: Option<String>,
+    threat_status: Option<&'a ThreatStatus>,
+    threat_status_source: Option<&'a str>,
+    published_in: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct MothSynonymRow<'a> {
+    synonym_taxon_id: &'a str,
+    synonym_name: String,
+    genus: &'a str,
+    epithet: &'a str,
+    accepted_taxon_id: &'a str,
+    accepted_name: String,
+}
+
+/// One row per species with the classification flattened and common names pipe-joined.
+pub fn write_moth_data(
+    output_file_path: &str,
+    delimiter: u8,
+    moth_entries: &[SpeciesData],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(output_file_path)?;
+    for species in moth_entries {
+        let classification = &species.classification;
+        let species_profile = species.species_profile.as_ref();
+        let distribution = species.distribution.as_ref();
+        writer.serialize(MothDataRow {
+            catalogue_of_life_taxon_id: &species.catalogue_of_life_taxon_id,
+            scientific_name: species_name(species),
+            superfamily: classification.superfamily.as_deref(),
+            family: classification.family.as_deref(),
+            subfamily: classification.subfamily.as_deref(),
+            tribe: classification.tribe.as_deref(),
+            subtribe: classification.subtribe.as_deref(),
+            genus: &classification.genus,
+            epithet: &classification.epithet,
+            generic_name: classification.generic_name.as_deref(),
+            common_names: species
+                .common_names
+                .as_ref()
+                .map(|x| x.join(LIST_SEPARATOR)),
+            synonyms_count: species.synonyms.as_ref().map_or(0, |x| x.len()),
+            extinct: species_profile.and_then(|x| x.extinct),
+            freshwater: species_profile.and_then(|x| x.freshwater),
+            marine: species_profile.and_then(|x| x.marine),
+            terrestrial: species_profile.and_then(|x| x.terrestrial),
+            habitat: species_profile.and_then(|x| x.habitat),
+            locality: distribution.and_then(|x| x.locality.as_deref()),
+            localities: distribution
+                .and_then(|x| x.localities.as_ref())
+                .map(|x| x.join(LIST_SEPARATOR)),
+            country_codes: distribution
+                .and_then(|x| x.country_codes.as_ref())
+                .map(|x| x.join(LIST_SEPARATOR)),
+            threat_status: distribution.and_then(|x| x.threat_status.as_ref()),
+            threat_status_source: distribution.and_then(|x| x.source.as_deref()),
+            published_in: species.published_in.as_deref(),
+        })?;
+    }
+    writer.flush()?;
+    return Ok(());
+}
+
+/// One row per synonym with the accepted species it belongs to.
+pub fn write_moth_synonyms(
+    output_file_path: &str,
+    delimiter: u8,
+    moth_entries: &[SpeciesData],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(output_file_path)?;
+    for species in moth_entries {
+        for synonym in species.synonyms.iter().flatten() {
+            writer.serialize(MothSynonymRow {
+                synonym_taxon_id: &synonym.catalogue_of_life_taxon_id,
+                synonym_name: format!("{} {}", synonym.genus, synonym.epithet),
+                genus: &synonym.genus,
+                epithet: &synonym.epithet,
+                accepted_taxon_id: &species.catalogue_of_life_taxon_id,
+                accepted_name: species_name(species),
+            })?;
+        }
+    }
+    writer.flush()?;
+    return Ok(());
+}
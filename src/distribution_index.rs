@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use crate::SpeciesData;
+
+/// Country code or location id -> taxon ids of the species recorded there, in `moth_data` order.
+pub type DistributionIndex = BTreeMap<String, Vec<String>>;
+
+/// Indexes species by the ISO 3166 alpha-2 country codes of their distribution.
+pub fn country_index(moth_data: &[SpeciesData]) -> DistributionIndex {
+    return distribution_index(moth_data, |x| {
+        x.distribution
+            .as_ref()
+            .and_then(|x| x.country_codes.as_ref())
+    });
+}
+
+/// Indexes species by the location ids (`iso:DE`, `tdwg:1`, ...) of their distribution.
+pub fn location_index(moth_data: &[SpeciesData]) -> DistributionIndex {
+    return distribution_index(moth_data, |x| {
+        x.distribution
+            .as_ref()
+            .and_then(|x| x.location_ids.as_ref())
+    });
+}
+
+fn distribution_index(
+    moth_data: &[SpeciesData],
+    codes: fn(&SpeciesData) -> Option<&Vec<String>>,
+) -> DistributionIndex {
+    let mut index: DistributionIndex = BTreeMap::new();
+    for species in moth_data {
+        for code in codes(species).into_iter().flatten() {
+            index
+                .entry(code.clone())
+                .or_default()
+                .push(species.catalogue_of_life_taxon_id.clone());
+        }
+    }
+    return index;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Distribution, test_data::species};
+
+    fn recorded_in(
+        taxon_id: &str,
+        epithet: &str,
+        country_codes: &[&str],
+        location_ids: &[&str],
+    ) -> SpeciesData {
+        let codes = |codes: &[&str]| -> Option<Vec<String>> {
+            return (!codes.is_empty()).then(|| codes.iter().map(|x| x.to_string()).collect());
+        };
+        let mut species = species(taxon_id, "Agrotis", epithet);
+        species.distribution = Some(Distribution {
+            locality: None,
+            localities: None,
+            threat_status: None,
+            source: None,
+            country_codes: codes(country_codes),
+            location_ids: codes(location_ids),
+        });
+        return species;
+    }
+
+    #[test]
+    fn indexes_species_by_country_and_location() {
+        let moth_data = vec![
+            recorded_in("3", "ipsilon", &["US", "GB"], &["iso:GB", "tdwg:1"]),
+            species("4", "Agrotis", "alba"),
+            recorded_in("1", "segetum", &["GB"], &[]),
+            recorded_in("2", "exclamationis", &[], &["tdwg:1"]),
+        ];
+
+        assert_eq!(
+            country_index(&moth_data).into_iter().collect::<Vec<_>>(),
+            [
+                ("GB".to_string(), vec!["3".to_string(), "1".to_string()]),
+                ("US".to_string(), vec!["3".to_string()]),
+            ]
+        );
+        assert_eq!(
+            location_index(&moth_data).into_iter().collect::<Vec<_>>(),
+            [
+                ("iso:GB".to_string(), vec!["3".to_string()]),
+                ("tdwg:1".to_string(), vec!["3".to_string(), "2".to_string()]),
+            ]
+        );
+        assert!(country_index(&[]).is_empty());
+    }
+}
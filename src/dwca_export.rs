@@ -0,0 +1,415 @@
+use std::{collections::HashSet, error::Error, fs::File, io::Write, path::Path};
+
+use moth_filter::*;
+
+const TAXON_ROW_TYPE: &str = "http://rs.tdwg.org/dwc/terms/Taxon";
+const EXTENSIONS: [(&str, &str); 3] = [
+    (
+        "VernacularName.tsv",
+        "http://rs.gbif.org/terms/1.0/VernacularName",
+    ),
+    (
+        "SpeciesProfile.tsv",
+        "http://rs.gbif.org/terms/1.0/SpeciesProfile",
+    ),
+    (
+        "Distribution.tsv",
+        "http://rs.gbif.org/terms/1.0/Distribution",
+    ),
+];
+const TERM_NAMESPACES: [(&str, &str); 6] = [
+    ("dwc:", "http://rs.tdwg.org/dwc/terms/"),
+    ("dcterms:", "http://purl.org/dc/terms/"),
+    ("gbif:", "http://rs.gbif.org/terms/1.0/"),
+    ("iucn:", "http://iucn.org/terms/"),
+    ("col:", "https://terms.catalogueoflife.org/"),
+    ("clb:", "https://terms.checklistbank.org/"),
+];
+const TAXON_ID_TERM: &str = "dwc:taxonID";
+const PARENT_ID_TERM: &str = "dwc:parentNameUsageID";
+
+#[derive(Default)]
+pub struct DwcaCounts {
+    pub taxon_rows: usize,
+    pub extension_rows: usize,
+    /// Every file written to the archive directory.
+    pub file_paths: Vec<String>,
+}
+
+/// Writes accepted moths, their synonyms and the matching extension rows from `data_dir` as a Darwin Core Archive in `output_dir`.
+pub fn write_dwca(
+    data_dir: &str,
+    output_dir: &str,
+    moth_entries: &[SpeciesData],
+) -> Result<DwcaCounts, Box<dyn Error>> {
+    std::fs::create_dir_all(output_dir)?;
+    let mut taxon_ids: HashSet<&str> = HashSet::new();
+    for species in moth_entries {
+        taxon_ids.insert(&species.catalogue_of_life_taxon_id);
+        for synonym in species.synonyms.iter().flatten() {
+            taxon_ids.insert(&synonym.catalogue_of_life_taxon_id);
+        }
+    }
+
+    let mut counts: DwcaCounts = Default::default();
+    let mut meta_xml_files: Vec<String> = Vec::new();
+
+    let taxon_output_file_path = format!("{output_dir}/Taxon.tsv");
+    let (taxon_headers, taxon_rows) = copy_matching_rows(
+        &format!("{data_dir}/Taxon.tsv"),
+        &taxon_output_file_path,
+        &taxon_ids,
+    )?;
+    counts.taxon_rows = taxon_rows;
+    counts.file_paths.push(taxon_output_file_path);
+    meta_xml_files.push(meta_xml_file(
+        "core",
+        "Taxon.tsv",
+        TAXON_ROW_TYPE,
+        &taxon_headers,
+    )?);
+
+    for (file_name, row_type) in EXTENSIONS {
+        let input_file_path = format!("{data_dir}/{file_name}");
+        if !Path::new(&input_file_path).exists() {
+            continue;
+        }
+        let output_file_path = format!("{output_dir}/{file_name}");
+        let (headers, rows) = copy_matching_rows(&input_file_path, &output_file_path, &taxon_ids)?;
+        counts.extension_rows += rows;
+        counts.file_paths.push(output_file_path);
+        meta_xml_files.push(meta_xml_file("extension", file_name, row_type, &headers)?);
+    }
+
+    let meta_xml_file_path = format!("{output_dir}/meta.xml");
+    let mut meta_xml = File::create(&meta_xml_file_path)?;
+    writeln!(meta_xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        meta_xml,
+        "<archive xmlns=\"http://rs.tdwg.org/dwc/text/\" metadata=\"eml.xml\">"
+    )?;
+    for meta_xml_file in meta_xml_files {
+        write!(meta_xml, "{meta_xml_file}")?;
+    }
+    writeln!(meta_xml, "</archive>")?;
+    counts.file_paths.push(meta_xml_file_path);
+
+    let source_title = read_source_title(data_dir);
+    // EML requires a contact, the source's one is the right place for questions about the data
+    let source_contact = read_eml_element_xml(data_dir, "contact");
+    let eml_xml_file_path = format!("{output_dir}/eml.xml");
+    let mut eml_xml = File::create(&eml_xml_file_path)?;
+    write!(
+        eml_xml,
+        "{}",
+        eml_xml_content(source_title.as_deref(), source_contact.as_deref())
+    )?;
+    counts.file_paths.push(eml_xml_file_path);
+
+    return Ok(counts);
+}
+
+// copies the header and every row whose taxon id is in `taxon_ids`, dropping parent links that point outside of the subset
+fn copy_matching_rows(
+    input_file_path: &str,
+    output_file_path: &str,
+    taxon_ids: &HashSet<&str>,
+) -> Result<(csv::StringRecord, usize), Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .quoting(false)
+        .from_path(input_file_path)?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .quote_style(csv::QuoteStyle::Never)
+        .from_path(output_file_path)?;
+    let headers = reader.headers()?.clone();
+    let taxon_id_index = headers
+        .iter()
+        .position(|x| x == TAXON_ID_TERM)
+        .ok_or(format!("No {TAXON_ID_TERM} column in {input_file_path}"))?;
+    let parent_id_index = headers.iter().position(|x| x == PARENT_ID_TERM);
+    writer.write_record(&headers)?;
+
+    let mut row_count = 0;
+    for record_result in reader.records() {
+        let Ok(record) = record_result else {
+            continue;
+        };
+        if !taxon_ids.contains(record.get(taxon_id_index).unwrap_or_default()) {
+            continue;
+        }
+        match parent_id_index {
+            Some(parent_id_index)
+                if !taxon_ids.contains(record.get(parent_id_index).unwrap_or_default()) =>
+            {
+                let record_without_parent: csv::StringRecord = record
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| if i == parent_id_index { "" } else { x })
+                    .collect();
+                writer.write_record(&record_without_parent)?;
+            }
+            _ => writer.write_record(&record)?,
+        }
+        row_count += 1;
+    }
+    writer.flush()?;
+    return Ok((headers, row_count));
+}
+
+fn meta_xml_file(
+    element: &str,
+    file_name: &str,
+    row_type: &str,
+    headers: &csv::StringRecord,
+) -> Result<String, Box<dyn Error>> {
+    let id_element = if element == "core" { "id" } else { "coreid" };
+    let taxon_id_index = headers
+        .iter()
+        .position(|x| x == TAXON_ID_TERM)
+        .ok_or(format!("No {TAXON_ID_TERM} column in {file_name}"))?;
+    let mut meta_xml_file = format!(
+        "  <{element} encoding=\"UTF-8\" fieldsTerminatedBy=\"\\t\" linesTerminatedBy=\"\\n\" fieldsEnclosedBy=\"\" ignoreHeaderLines=\"1\" rowType=\"{row_type}\">\n"
+    );
+    meta_xml_file +=
+        &format!("    <files>\n      <location>{file_name}</location>\n    </files>\n");
+    meta_xml_file += &format!("    <{id_element} index=\"{taxon_id_index}\"/>\n");
+    for (index, header) in headers.iter().enumerate() {
+        meta_xml_file += &format!(
+            "    <field index=\"{index}\" term=\"{}\"/>\n",
+            term_uri(header)
+        );
+    }
+    meta_xml_file += &format!("  </{element}>\n");
+    return Ok(meta_xml_file);
+}
+
+fn term_uri(header: &str) -> String {
+    for (prefix, namespace) in TERM_NAMESPACES {
+        if let Some(term) = header.strip_prefix(prefix) {
+            return format!("{namespace}{term}");
+        }
+    }
+    return header.to_string();
+}
+
+// title of the source release from its `eml.xml`, if there is one
+fn read_source_title(data_dir: &str) -> Option<String> {
+    return read_eml_element(data_dir, "title");
+}
+
+/// Text of the first `element` in the source release's `eml.xml`, if there is one.
+pub fn read_eml_element(data_dir: &str, element: &str) -> Option<String> {
+    return read_eml_element_xml(data_dir, element).map(|x| xml_unescape(&x));
+}
+
+// content of the first `element` as is, child elements included
+fn read_eml_element_xml(data_dir: &str, element: &str) -> Option<String> {
+    let eml_xml = std::fs::read_to_string(format!("{data_dir}/eml.xml")).ok()?;
+    return eml_element_xml(&eml_xml, element);
+}
+
+// matches the whole tag name so `contact` doesn't find `<contactPerson>`, with or without a namespace prefix
+fn eml_element_xml(eml_xml: &str, element: &str) -> Option<String> {
+    let mut search_start = 0;
+    while let Some(tag_offset) = eml_xml[search_start..].find('<') {
+        let tag_start = search_start + tag_offset + 1;
+        search_start = tag_start;
+        let tag = &eml_xml[tag_start..];
+        let tag_name_end = tag.find(|x: char| x == '>' || x == '/' || x.is_whitespace())?;
+        let tag_name = &tag[..tag_name_end];
+        if tag_name.rsplit(':').next() != Some(element) {
+            continue;
+        }
+        let tag_end = tag_start + tag.find('>')?;
+        // `<element/>` has no content, a later one might
+        if eml_xml[..tag_end].ends_with('/') {
+            continue;
+        }
+        let content_start = tag_end + 1;
+        let content_end =
+            content_start + eml_xml[content_start..].find(&format!("</{tag_name}>"))?;
+        return Some(eml_xml[content_start..content_end].trim().to_string());
+    }
+    return None;
+}
+
+fn eml_xml_content(source_title: Option<&str>, source_contact: Option<&str>) -> String {
+    let source_title = source_title.unwrap_or("Catalogue of Life");
+    let contact = source_contact.unwrap_or("<organizationName>moth_filter</organizationName>");
+    let title = xml_escape(&format!(
+        "Moths (Lepidoptera excluding Papilionoidea) from {source_title}"
+    ));
+    let description = xml_escape(&format!(
+        "Species rank moth names with their synonyms, vernacular names, species profiles and distributions, filtered from {source_title} by moth_filter {}.",
+        env!("CARGO_PKG_VERSION")
+    ));
+    return format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<eml:eml xmlns:eml=\"https://eml.ecoinformatics.org/eml-2.2.0\" packageId=\"moth_filter\" system=\"moth_filter\">
+  <dataset>
+    <title>{title}</title>
+    <creator>
+      <organizationName>moth_filter</organizationName>
+    </creator>
+    <pubDate>{}</pubDate>
+    <abstract>
+      <para>{description}</para>
+    </abstract>
+    <intellectualRights>
+      <para>See the license of the source dataset: {}</para>
+    </intellectualRights>
+    <contact>
+      {contact}
+    </contact>
+  </dataset>
+</eml:eml>
+",
+        today_iso_date(),
+        xml_escape(source_title)
+    );
+}
+
+// `YYYY-MM-DD` in UTC, converted from days since the unix epoch
+fn today_iso_date() -> String {
+    let unix_days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() / 86_400) as i64;
+    // civil date from days, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = unix_days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!("{year:04}-{month:02}-{day:02}");
+}
+
+fn xml_escape(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+fn xml_unescape(text: &str) -> String {
+    return text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn reads_whole_eml_elements() {
+        let eml_xml = "<eml:eml><dataset><titleAbbreviation>COL</titleAbbreviation><dc:title xml:lang=\"en\"> A &amp; B </dc:title><contactPerson>x</contactPerson><contact/><contact><organizationName>COL</organizationName></contact></dataset></eml:eml>";
+        assert_eq!(
+            eml_element_xml(eml_xml, "title").as_deref(),
+            Some("A &amp; B")
+        );
+        assert_eq!(
+            eml_element_xml(eml_xml, "contact").as_deref(),
+            Some("<organizationName>COL</organizationName>")
+        );
+        assert_eq!(
+            eml_element_xml(eml_xml, "titleAbbreviation").as_deref(),
+            Some("COL")
+        );
+        assert_eq!(eml_element_xml(eml_xml, "pubDate"), None);
+        assert_eq!(
+            read_eml_element(FIXTURE_DATA_DIR, "title").as_deref(),
+            Some("Catalogue of Life & Friends")
+        );
+    }
+
+    #[test]
+    fn writes_moth_subset_archive() {
+        let moth_entries: Vec<SpeciesData> = serde_json::from_str(
+            r#"[
+                {
+                    "catalogue_of_life_taxon_id": "6XQTZ",
+                    "classification": {"genus": "Agrotis", "epithet": "ipsilon"},
+                    "synonyms": [
+                        {"catalogue_of_life_taxon_id": "P4L6S", "genus": "Feltia", "epithet": "ipsilon"},
+                        {"catalogue_of_life_taxon_id": "P5M9T", "genus": "Agrotis", "epithet": "suffusa"}
+                    ]
+                },
+                {"catalogue_of_life_taxon_id": "3FQ8H", "classification": {"genus": "Tuta", "epithet": "absoluta"}}
+            ]"#,
+        )
+        .unwrap();
+        let output_dir =
+            std::env::temp_dir().join(format!("moth_filter_dwca_{}", std::process::id()));
+        let output_dir = output_dir.to_string_lossy().to_string();
+        let counts = write_dwca(FIXTURE_DATA_DIR, &output_dir, &moth_entries).unwrap();
+        let read_output = |file_name: &str| {
+            return std::fs::read_to_string(format!("{output_dir}/{file_name}")).unwrap();
+        };
+        let taxon_tsv = read_output("Taxon.tsv");
+        let vernacular_name_tsv = read_output("VernacularName.tsv");
+        let meta_xml = read_output("meta.xml");
+        let eml_xml = read_output("eml.xml");
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(counts.taxon_rows, 4);
+        // 3 vernacular names, 1 species profile and 3 distribution rows
+        assert_eq!(counts.extension_rows, 7);
+        assert_eq!(counts.file_paths.len(), 6);
+
+        let taxon_rows: Vec<Vec<&str>> = taxon_tsv
+            .lines()
+            .skip(1)
+            .map(|x| x.split('\t').collect())
+            .collect();
+        let taxon_ids: Vec<&str> = taxon_rows.iter().map(|x| x[0]).collect();
+        assert_eq!(taxon_ids, ["6XQTZ", "3FQ8H", "P4L6S", "P5M9T"]);
+        // the genus is not part of the archive, synonyms keep their accepted species as parent
+        assert_eq!(taxon_rows[0][1], "");
+        assert_eq!(taxon_rows[2][1], "6XQTZ");
+        assert_eq!(taxon_rows[2][2], "6XQTZ");
+
+        assert!(
+            vernacular_name_tsv
+                .lines()
+                .skip(1)
+                .all(|x| x.starts_with("6XQTZ\t"))
+        );
+
+        assert!(meta_xml.contains("  <core encoding=\"UTF-8\" fieldsTerminatedBy=\"\\t\" linesTerminatedBy=\"\\n\" fieldsEnclosedBy=\"\" ignoreHeaderLines=\"1\" rowType=\"http://rs.tdwg.org/dwc/terms/Taxon\">\n    <files>\n      <location>Taxon.tsv</location>\n    </files>\n    <id index=\"0\"/>\n    <field index=\"0\" term=\"http://rs.tdwg.org/dwc/terms/taxonID\"/>\n    <field index=\"1\" term=\"http://rs.tdwg.org/dwc/terms/parentNameUsageID\"/>\n"));
+        assert!(meta_xml.contains(
+            "    <field index=\"10\" term=\"https://terms.catalogueoflife.org/notho\"/>\n"
+        ));
+        assert!(meta_xml.contains(
+            "    <field index=\"33\" term=\"https://terms.checklistbank.org/merged\"/>\n  </core>\n"
+        ));
+        assert!(meta_xml.contains("rowType=\"http://rs.gbif.org/terms/1.0/VernacularName\">\n    <files>\n      <location>VernacularName.tsv</location>\n    </files>\n    <coreid index=\"0\"/>\n    <field index=\"0\" term=\"http://rs.tdwg.org/dwc/terms/taxonID\"/>\n    <field index=\"1\" term=\"http://purl.org/dc/terms/language\"/>\n    <field index=\"2\" term=\"http://rs.tdwg.org/dwc/terms/vernacularName\"/>\n"));
+        assert!(
+            meta_xml
+                .contains("    <field index=\"3\" term=\"http://iucn.org/terms/threatStatus\"/>\n")
+        );
+        assert_eq!(meta_xml.matches("<extension ").count(), 3);
+        assert_eq!(meta_xml.matches("<coreid index=\"0\"/>").count(), 3);
+
+        assert!(eml_xml.contains("<title>Moths (Lepidoptera excluding Papilionoidea) from Catalogue of Life &amp; Friends</title>"));
+        assert!(eml_xml.contains(
+            "<electronicMailAddress>support@catalogueoflife.org</electronicMailAddress>"
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SpeciesData, parse_scientific_name};
+
+// ordered longest first so `-rum` is stripped before `-um`
+const LATIN_GENDER_ENDINGS: [&str; 11] = [
+    "rum", "ium", "ius", "ra", "er", "ia", "um", "us", "is", "a", "e",
+];
+/// Lowest score that still reads as a plausible misspelling of the matched name.
+pub const DEFAULT_FUZZY_MIN_SCORE: f64 = 0.7;
+const MIN_STEM_LENGTH: usize = 3;
+// penalty for an epithet that only matches after gender-ending normalization
+const GENDER_VARIANT_PENALTY: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzyMatch {
+    /// Taxon id of the accepted species, also for matches on a synonym
+    pub catalogue_of_life_taxon_id: String,
+    pub matched_name: String,
+    pub accepted_name: String,
+    pub synonym: bool,
+    /// 1.0 is an exact match
+    pub score: f64,
+}
+
+struct MatcherName {
+    name: String,
+    epithet: String,
+    epithet_stem: String,
+    accepted_index: usize,
+    synonym: bool,
+}
+
+/// Index over accepted names and synonyms of the moth data for repeated fuzzy lookups.
+pub struct FuzzyMatcher {
+    accepted: Vec<(String, String)>,
+    genera: HashMap<String, Vec<MatcherName>>,
+}
+
+impl FuzzyMatcher {
+    pub fn new(moth_data: &[SpeciesData]) -> FuzzyMatcher {
+        let mut accepted = Vec::new();
+        let mut genera: HashMap<String, Vec<MatcherName>> = HashMap::new();
+        for species in moth_data {
+            let accepted_index = accepted.len();
+            let genus = &species.classification.genus;
+            let epithet = &species.classification.epithet;
+            accepted.push((
+                species.catalogue_of_life_taxon_id.clone(),
+                format!("{} {}", genus, epithet),
+            ));
+            add_name(&mut genera, genus, epithet, accepted_index, false);
+            // the name as published, matched like an accepted name
+            if let Some(generic_name) = &species.classification.generic_name {
+                add_name(&mut genera, generic_name, epithet, accepted_index, false);
+            }
+            for synonym in species.synonyms.iter().flatten() {
+                add_name(
+                    &mut genera,
+                    &synonym.genus,
+                    &synonym.epithet,
+                    accepted_index,
+                    true,
+                );
+            }
+        }
+        return FuzzyMatcher { accepted, genera };
+    }
+
+    /// Ranked candidates for a scientific name query, best first. Authorship and qualifiers in the query are ignored.
+    pub fn find_matches(&self, query: &str, min_score: f64, limit: usize) -> Vec<FuzzyMatch> {
+        let Some(parsed_query) = parse_scientific_name(query) else {
+            return Vec::new();
+        };
+        let Some(query_epithet) = parsed_query.epithet else {
+            return Vec::new();
+        };
+        let query_genus = parsed_query.genus.to_lowercase();
+        let query_epithet = query_epithet.to_lowercase();
+        let query_epithet_stem = normalize_latin_epithet(&query_epithet);
+        let query_length = query_genus.chars().count() + query_epithet.chars().count();
+        let max_genus_distance = (query_genus.chars().count() / 4).max(1);
+
+        let mut matches: Vec<FuzzyMatch> = Vec::new();
+        for (genus, names) in self.genera.iter() {
+            let genus_distance = edit_distance(&query_genus, genus);
+            if genus_distance > max_genus_distance {
+                continue;
+            }
+            for name in names {
+                let epithet_distance = if name.epithet == query_epithet {
+                    0.0
+                } else {
+                    let raw_distance = edit_distance(&query_epithet, &name.epithet) as f64;
+                    let stem_distance = edit_distance(&query_epithet_stem, &name.epithet_stem)
+                        as f64
+                        + GENDER_VARIANT_PENALTY;
+                    raw_distance.min(stem_distance)
+                };
+                let name_length = genus.chars().count() + name.epithet.chars().count();
+                let distance = genus_distance as f64 + epithet_distance;
+                let score = 1.0 - distance / query_length.max(name_length) as f64;
+                if score < min_score {
+                    continue;
+                }
+                let (taxon_id, accepted_name) = &self.accepted[name.accepted_index];
+                matches.push(FuzzyMatch {
+                    catalogue_of_life_taxon_id: taxon_id.clone(),
+                    matched_name: name.name.clone(),
+                    accepted_name: accepted_name.clone(),
+                    synonym: name.synonym,
+                    score,
+                });
+            }
+        }
+
+        matches.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.synonym.cmp(&b.synonym))
+                .then(a.matched_name.cmp(&b.matched_name))
+        });
+        matches.truncate(limit);
+        return matches;
+    }
+}
+
+fn add_name(
+    genera: &mut HashMap<String, Vec<MatcherName>>,
+    genus: &str,
+    epithet: &str,
+    accepted_index: usize,
+    synonym: bool,
+) {
+    let epithet_lowercase = epithet.to_lowercase();
+    genera
+        .entry(genus.to_lowercase())
+        .or_default()
+        .push(MatcherName {
+            name: format!("{} {}", genus, epithet),
+            epithet_stem: normalize_latin_epithet(&epithet_lowercase),
+            epithet: epithet_lowercase,
+            accepted_index,
+            synonym,
+        });
+}
+
+/// Strips Latin gender endings so that e.g. `nigra`, `niger` and `nigrum` compare equal.
+pub fn normalize_latin_epithet(epithet: &str) -> String {
+    let epithet = epithet.to_lowercase();
+    for ending in LATIN_GENDER_ENDINGS {
+        if let Some(stem) = epithet.strip_suffix(ending)
+            && stem.chars().count() >= MIN_STEM_LENGTH
+        {
+            return stem.to_string();
+        }
+    }
+    return epithet;
+}
+
+/// Levenshtein distance over chars.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current_row: Vec<usize> = vec![0; b_chars.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current_row[0] = i + 1;
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            current_row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    return previous_row[b_chars.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{species, synonym};
+
+    #[test]
+    fn computes_edit_distance() {
+        assert_eq!(edit_distance("luna", "luna"), 0);
+        assert_eq!(edit_distance("lunna", "luna"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("ä", "a"), 1);
+    }
+
+    #[test]
+    fn strips_latin_gender_endings() {
+        assert_eq!(normalize_latin_epithet("nupta"), "nupt");
+        assert_eq!(normalize_latin_epithet("Nuptus"), "nupt");
+        assert_eq!(normalize_latin_epithet("nigra"), "nig");
+        assert_eq!(normalize_latin_epithet("niger"), "nig");
+        assert_eq!(normalize_latin_epithet("nigrum"), "nig");
+        // the stem would be shorter than `MIN_STEM_LENGTH`
+        assert_eq!(normalize_latin_epithet("ra"), "ra");
+    }
+
+    #[test]
+    fn matches_one_letter_typo() {
+        let moth_data = vec![
+            species("1", "Actias", "luna"),
+            species("2", "Catocala", "nupta"),
+        ];
+        let matches =
+            FuzzyMatcher::new(&moth_data).find_matches("Actias lunna", DEFAULT_FUZZY_MIN_SCORE, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].catalogue_of_life_taxon_id, "1");
+        assert_eq!(matches[0].matched_name, "Actias luna");
+        assert!((matches[0].score - (1.0 - 1.0 / 11.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matches_exact_name_with_authorship() {
+        let moth_data = vec![species("1", "Actias", "luna")];
+        let matches = FuzzyMatcher::new(&moth_data).find_matches(
+            "Actias luna (Linnaeus, 1758)",
+            DEFAULT_FUZZY_MIN_SCORE,
+            10,
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].score, 1.0);
+    }
+
+    #[test]
+    fn matches_gender_variant() {
+        let moth_data = vec![species("1", "Catocala", "nupta")];
+        let matches = FuzzyMatcher::new(&moth_data).find_matches(
+            "Catocala nuptus",
+            DEFAULT_FUZZY_MIN_SCORE,
+            10,
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].matched_name, "Catocala nupta");
+        // only the gender variant penalty instead of the raw distance of 2
+        assert!((matches[0].score - (1.0 - GENDER_VARIANT_PENALTY / 14.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn skips_genus_beyond_distance_cap() {
+        let moth_data = vec![species("1", "Actias", "luna")];
+        let matcher = FuzzyMatcher::new(&moth_data);
+        // one edit is allowed for a six letter genus, two are not even without a minimum score
+        assert_eq!(matcher.find_matches("Actiax luna", 0.0, 10).len(), 1);
+        assert!(matcher.find_matches("Actixx luna", 0.0, 10).is_empty());
+    }
+
+    #[test]
+    fn drops_matches_below_min_score() {
+        let moth_data = vec![species("1", "Catocala", "nupta")];
+        let matcher = FuzzyMatcher::new(&moth_data);
+        assert!(
+            matcher
+                .find_matches("Catocala sponsa", DEFAULT_FUZZY_MIN_SCORE, 10)
+                .is_empty()
+        );
+        assert_eq!(matcher.find_matches("Catocala sponsa", 0.0, 10).len(), 1);
+    }
+
+    #[test]
+    fn ranks_accepted_before_synonym_with_equal_score() {
+        let mut moth_data = vec![
+            species("1", "Catocala", "nuptb"),
+            species("2", "Catocala", "sponsa"),
+        ];
+        moth_data[1].synonyms = Some(vec![synonym("3", "Catocala", "nupta")]);
+        let matches = FuzzyMatcher::new(&moth_data).find_matches(
+            "Catocala nuptx",
+            DEFAULT_FUZZY_MIN_SCORE,
+            10,
+        );
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].score, matches[1].score);
+        assert_eq!(matches[0].matched_name, "Catocala nuptb");
+        assert!(!matches[0].synonym);
+        assert_eq!(matches[1].matched_name, "Catocala nupta");
+        assert!(matches[1].synonym);
+        assert_eq!(matches[1].accepted_name, "Catocala sponsa");
+        assert_eq!(matches[1].catalogue_of_life_taxon_id, "2");
+
+        assert_eq!(
+            FuzzyMatcher::new(&moth_data)
+                .find_matches("Catocala nuptx", DEFAULT_FUZZY_MIN_SCORE, 1)
+                .len(),
+            1
+        );
+    }
+}
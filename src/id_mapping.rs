@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SpeciesData, species_name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdMappingReason {
+    /// The taxon id is still an accepted moth.
+    Unchanged,
+    /// The id is gone but the same name is accepted under a new id, with the same authorship when both are known.
+    NameMatch,
+    /// The old species is now a synonym of one accepted species.
+    Synonymized,
+    /// The old species is now a synonym of several accepted species.
+    Split,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdMapping {
+    pub old_catalogue_of_life_taxon_id: String,
+    pub old_name: String,
+    pub new_catalogue_of_life_taxon_ids: Vec<String>,
+    pub reason: IdMappingReason,
+}
+
+/// Maps every accepted taxon id of a previous output to the ids in the new one.
+pub fn map_taxon_ids(
+    old_moth_data: &[SpeciesData],
+    new_moth_data: &[SpeciesData],
+) -> Vec<IdMapping> {
+    let mut new_by_id: HashMap<&str, &SpeciesData> = HashMap::new();
+    let mut new_by_name: HashMap<String, Vec<&SpeciesData>> = HashMap::new();
+    // synonym id or name -> accepted ids it is a synonym of
+    let mut new_synonym_by_id: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut new_synonym_by_name: HashMap<String, Vec<&str>> = HashMap::new();
+    for species in new_moth_data {
+        let taxon_id = species.catalogue_of_life_taxon_id.as_str();
+        new_by_id.insert(taxon_id, species);
+        new_by_name
+            .entry(species_name(species))
+            .or_default()
+            .push(species);
+        for synonym in species.synonyms.iter().flatten() {
+            new_synonym_by_id
+                .entry(&synonym.catalogue_of_life_taxon_id)
+                .or_default()
+                .push(taxon_id);
+            new_synonym_by_name
+                .entry(format!("{} {}", synonym.genus, synonym.epithet))
+                .or_default()
+                .push(taxon_id);
+        }
+    }
+
+    let mut id_mappings: Vec<IdMapping> = Vec::with_capacity(old_moth_data.len());
+    for old_species in old_moth_data {
+        let old_taxon_id = &old_species.catalogue_of_life_taxon_id;
+        let old_name = species_name(old_species);
+        let id_mapping = |new_taxon_ids: Vec<&str>, reason: IdMappingReason| IdMapping {
+            old_catalogue_of_life_taxon_id: old_taxon_id.clone(),
+            old_name: old_name.clone(),
+            new_catalogue_of_life_taxon_ids: new_taxon_ids.into_iter().map(String::from).collect(),
+            reason,
+        };
+
+        if new_by_id.contains_key(old_taxon_id.as_str()) {
+            id_mappings.push(id_mapping(vec![old_taxon_id], IdMappingReason::Unchanged));
+            continue;
+        }
+
+        // homonyms are told apart by authorship, a name with a different known authorship is a different taxon
+        let name_match = new_by_name.get(&old_name).and_then(|name_matches| {
+            if old_species.authorship.is_none() {
+                return name_matches.first();
+            }
+            return name_matches
+                .iter()
+                .find(|x| x.authorship == old_species.authorship)
+                .or_else(|| name_matches.iter().find(|x| x.authorship.is_none()));
+        });
+        if let Some(new_species) = name_match {
+            id_mappings.push(id_mapping(
+                vec![&new_species.catalogue_of_life_taxon_id],
+                IdMappingReason::NameMatch,
+            ));
+            continue;
+        }
+
+        let mut accepted_taxon_ids: Vec<&str> = Vec::new();
+        for accepted_taxon_id in new_synonym_by_id
+            .get(old_taxon_id.as_str())
+            .into_iter()
+            .chain(new_synonym_by_name.get(&old_name))
+            .flatten()
+        {
+            if !accepted_taxon_ids.contains(accepted_taxon_id) {
+                accepted_taxon_ids.push(accepted_taxon_id);
+            }
+        }
+        let reason = match accepted_taxon_ids.len() {
+            0 => IdMappingReason::Deleted,
+            1 => IdMappingReason::Synonymized,
+            _ => IdMappingReason::Split,
+        };
+        id_mappings.push(id_mapping(accepted_taxon_ids, reason));
+    }
+    return id_mappings;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{species, synonym};
+
+    fn with_authorship(mut species: SpeciesData, authorship: &str) -> SpeciesData {
+        species.authorship = Some(authorship.to_string());
+        return species;
+    }
+
+    fn mapped(id_mapping: &IdMapping) -> (&str, Vec<&str>, IdMappingReason) {
+        return (
+            id_mapping.old_catalogue_of_life_taxon_id.as_str(),
+            id_mapping
+                .new_catalogue_of_life_taxon_ids
+                .iter()
+                .map(String::as_str)
+                .collect(),
+            id_mapping.reason,
+        );
+    }
+
+    #[test]
+    fn maps_unchanged_renumbered_and_deleted_ids() {
+        let old_moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("2", "Catocala", "sponsa"),
+            species("3", "Actias", "luna"),
+        ];
+        let new_moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("9", "Catocala", "sponsa"),
+        ];
+        let id_mappings = map_taxon_ids(&old_moth_data, &new_moth_data);
+        assert_eq!(
+            id_mappings.iter().map(mapped).collect::<Vec<_>>(),
+            [
+                ("1", vec!["1"], IdMappingReason::Unchanged),
+                ("2", vec!["9"], IdMappingReason::NameMatch),
+                ("3", vec![], IdMappingReason::Deleted),
+            ]
+        );
+        assert_eq!(id_mappings[1].old_name, "Catocala sponsa");
+    }
+
+    #[test]
+    fn maps_accepted_id_that_became_a_synonym() {
+        let old_moth_data = vec![
+            species("1", "Phalaena", "nupta"),
+            species("2", "Noctua", "ipsilon"),
+        ];
+        let mut catocala_nupta = species("5", "Catocala", "nupta");
+        // the old id is kept as the synonym id
+        catocala_nupta.synonyms = Some(vec![synonym("1", "Phalaena", "nupta")]);
+        let mut agrotis_ipsilon = species("6", "Agrotis", "ipsilon");
+        // a new synonym id, found by name
+        agrotis_ipsilon.synonyms = Some(vec![synonym("S7", "Noctua", "ipsilon")]);
+        let id_mappings = map_taxon_ids(&old_moth_data, &[catocala_nupta, agrotis_ipsilon]);
+        assert_eq!(
+            id_mappings.iter().map(mapped).collect::<Vec<_>>(),
+            [
+                ("1", vec!["5"], IdMappingReason::Synonymized),
+                ("2", vec!["6"], IdMappingReason::Synonymized),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_split_species_to_every_accepted_id() {
+        let old_moth_data = vec![species("1", "Agrotis", "segetum")];
+        let mut first = species("5", "Agrotis", "segetina");
+        first.synonyms = Some(vec![synonym("1", "Agrotis", "segetum")]);
+        let mut second = species("6", "Agrotis", "alia");
+        second.synonyms = Some(vec![synonym("S2", "Agrotis", "segetum")]);
+        let id_mappings = map_taxon_ids(&old_moth_data, &[first, second]);
+        assert_eq!(
+            mapped(&id_mappings[0]),
+            ("1", vec!["5", "6"], IdMappingReason::Split)
+        );
+    }
+
+    #[test]
+    fn tells_homonyms_apart_by_authorship() {
+        let old_moth_data = vec![
+            with_authorship(species("1", "Agrotis", "alba"), "Smith, 1900"),
+            with_authorship(species("2", "Agrotis", "alba"), "Jones, 1920"),
+            species("3", "Agrotis", "alba"),
+        ];
+        let new_moth_data = vec![
+            with_authorship(species("8", "Agrotis", "alba"), "Jones, 1920"),
+            with_authorship(species("9", "Agrotis", "alba"), "Smith, 1900"),
+        ];
+        let id_mappings = map_taxon_ids(&old_moth_data, &new_moth_data);
+        assert_eq!(
+            id_mappings.iter().map(mapped).collect::<Vec<_>>(),
+            [
+                ("1", vec!["9"], IdMappingReason::NameMatch),
+                ("2", vec!["8"], IdMappingReason::NameMatch),
+                // without authorship the first name match is taken
+                ("3", vec!["8"], IdMappingReason::NameMatch),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_homonym_with_different_authorship() {
+        let old_moth_data = vec![with_authorship(
+            species("1", "Agrotis", "alba"),
+            "Smith, 1900",
+        )];
+        let new_moth_data = vec![with_authorship(
+            species("8", "Agrotis", "alba"),
+            "Jones, 1920",
+        )];
+        assert_eq!(
+            mapped(&map_taxon_ids(&old_moth_data, &new_moth_data)[0]),
+            ("1", vec![], IdMappingReason::Deleted)
+        );
+        // a new name without authorship can still be the same taxon
+        let new_moth_data = vec![species("8", "Agrotis", "alba")];
+        assert_eq!(
+            mapped(&map_taxon_ids(&old_moth_data, &new_moth_data)[0]),
+            ("1", vec!["8"], IdMappingReason::NameMatch)
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpeciesData {
@@ -16,19 +16,40 @@ pub struct SpeciesData {
     pub synonyms: Option<Vec<SynonymSpecies>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub published_in: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorship: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Distribution {
+    /// The first `dwc:locality` of the taxon, as before distributions were merged.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub locality: Option<String>,
+    /// Every distinct `dwc:locality` of the taxon, in row order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localities: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub threat_status: Option<ThreatStatus>,
+    /// `dcterms:source` of the distribution, usually the assessment the threat status comes from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// ISO 3166 alpha-2, sorted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_codes: Option<Vec<String>>,
+    /// `gazetteer:code` like `iso:DE` or `tdwg:1`, sorted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_ids: Option<Vec<String>>,
 }
 
+/// `None` in `Distribution` means COL has no status, `NotEvaluated` means IUCN hasn't assessed the species.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ThreatStatus {
+    NotEvaluated,
+    DataDeficient,
     LeastConcern,
+    /// IUCN 2.3 "lower risk/conservation dependent", has no 3.1 equivalent.
+    ConservationDependent,
+    NearThreatened,
     Vulnerable,
     Endangered,
     CriticallyEndangered,
@@ -45,9 +66,46 @@ pub struct SpeciesProfile {
     pub freshwater: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub marine: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terrestrial: Option<bool>,
+    /// Derived from the flags above.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub habitat: Option<Habitat>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Habitat {
+    Terrestrial,
+    Freshwater,
+    Marine,
+    /// Freshwater and marine.
+    Brackish,
+    /// Terrestrial and freshwater or marine, usually aquatic larvae with terrestrial adults.
+    SemiAquatic,
+}
+
+impl Habitat {
+    /// `None` when no flag is set.
+    pub fn from_flags(
+        terrestrial: Option<bool>,
+        freshwater: Option<bool>,
+        marine: Option<bool>,
+    ) -> Option<Habitat> {
+        let freshwater = freshwater == Some(true);
+        let marine = marine == Some(true);
+        return match (terrestrial == Some(true), freshwater, marine) {
+            (true, false, false) => Some(Habitat::Terrestrial),
+            (true, _, _) => Some(Habitat::SemiAquatic),
+            (false, true, true) => Some(Habitat::Brackish),
+            (false, true, false) => Some(Habitat::Freshwater),
+            (false, false, true) => Some(Habitat::Marine),
+            (false, false, false) => None,
+        };
+    }
+}
+
+/// Ordered by rank, so sorting by it groups species by superfamily, then family and so on.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ScientificClassification {
     // somehow any of these (even genus and epithet) can be empty for a species
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -62,6 +120,15 @@ pub struct ScientificClassification {
     pub subtribe: Option<String>,
     pub genus: String,
     pub epithet: String,
+    /// `dwc:genericName` when it differs from the genus, the species was moved to `genus` but the name not recombined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generic_name: Option<String>,
+}
+
+impl ScientificClassification {
+    pub fn has_genus_mismatch(&self) -> bool {
+        return self.generic_name.is_some();
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,12 +140,66 @@ pub struct SynonymSpecies {
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ButterflyBlacklist {
+    #[serde(serialize_with = "serialize_sorted")]
     pub families: HashSet<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub subfamilies: HashSet<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub tribes: HashSet<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub subtribes: HashSet<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub genera: HashSet<String>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub epithets: HashSet<String>,
 }
 
 pub type MothSynonyms = HashMap<String, String>;
+
+/// `MothSynonyms` ordered by name, write this instead of the map itself so the outputs are identical between runs.
+pub fn sorted_moth_synonyms(moth_synonyms: &MothSynonyms) -> BTreeMap<&String, &String> {
+    return moth_synonyms.iter().collect();
+}
+
+// `HashSet` iteration order changes between runs
+fn serialize_sorted<S: Serializer>(
+    names: &HashSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    return serializer.collect_seq(names.iter().collect::<BTreeSet<&String>>());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_habitat_from_flags() {
+        assert_eq!(
+            Habitat::from_flags(Some(true), None, Some(false)),
+            Some(Habitat::Terrestrial)
+        );
+        assert_eq!(
+            Habitat::from_flags(Some(true), Some(true), None),
+            Some(Habitat::SemiAquatic)
+        );
+        assert_eq!(
+            Habitat::from_flags(Some(true), None, Some(true)),
+            Some(Habitat::SemiAquatic)
+        );
+        assert_eq!(
+            Habitat::from_flags(None, Some(true), None),
+            Some(Habitat::Freshwater)
+        );
+        assert_eq!(
+            Habitat::from_flags(Some(false), Some(false), Some(true)),
+            Some(Habitat::Marine)
+        );
+        assert_eq!(
+            Habitat::from_flags(None, Some(true), Some(true)),
+            Some(Habitat::Brackish)
+        );
+        assert_eq!(Habitat::from_flags(Some(false), Some(false), None), None);
+        assert_eq!(Habitat::from_flags(None, None, None), None);
+    }
+}
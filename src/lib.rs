@@ -1,5 +1,37 @@
+#![allow(clippy::needless_return)]
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+pub use binary_formats::*;
+pub use distribution_index::*;
+pub use fuzzy_matching::*;
+pub use id_mapping::*;
 pub use json_types::*;
+pub use moth_lint::*;
+pub use moth_lookup::*;
+pub use moth_stats::*;
+pub use name_parsing::*;
+#[cfg(feature = "serde_json")]
+pub use ndjson::*;
+pub use release_diff::*;
+#[cfg(all(feature = "zstd", feature = "serde_json"))]
+pub use zstd_records::*;
 
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+mod binary_formats;
+mod distribution_index;
+mod fuzzy_matching;
+mod id_mapping;
 mod json_types;
+mod moth_lint;
+mod moth_lookup;
+mod moth_stats;
+mod name_parsing;
+#[cfg(feature = "serde_json")]
+mod ndjson;
+mod release_diff;
+#[cfg(test)]
+mod test_data;
+#[cfg(all(feature = "zstd", feature = "serde_json"))]
+mod zstd_records;
 
 pub type MothDataJson = Vec<SpeciesData>;
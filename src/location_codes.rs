@@ -0,0 +1,154 @@
+// `dwc:locationID` is `gazetteer:code`, e.g. `iso:DE`, `iso:US-CA`, `tdwg:1`, `tdwg:GRB-OO`, `fao:27`
+
+const ISO_GAZETTEER: &str = "iso";
+const TDWG_GAZETTEER: &str = "tdwg";
+// officially assigned ISO 3166-1 alpha-2 codes, sorted for binary search
+const ISO_COUNTRY_CODES: [&str; 249] = [
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+// exceptionally reserved codes that show up in data instead of the assigned ones
+const ISO_COUNTRY_CODE_ALIASES: [(&str, &str); 2] = [("UK", "GB"), ("EL", "GR")];
+
+/// Assigned ISO 3166-1 alpha-2 code in upper case, `None` for user-assigned (`XX`, `ZZ`, ...) and unknown codes.
+pub fn normalize_country_code(country_code: &str) -> Option<String> {
+    let country_code = country_code.trim().to_ascii_uppercase();
+    if let Some((_, assigned_code)) = ISO_COUNTRY_CODE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == country_code)
+    {
+        return Some(assigned_code.to_string());
+    }
+    if ISO_COUNTRY_CODES
+        .binary_search(&country_code.as_str())
+        .is_err()
+    {
+        return None;
+    }
+    return Some(country_code);
+}
+
+/// Lowercase gazetteer and trimmed code, `None` without a gazetteer.
+/// ISO codes must be an assigned country, optionally with a subdivision (`iso:US-CA`).
+/// TDWG codes must be a level 1 (`1`), 2 (`12`), 3 (`GRB`) or 4 (`GRB-OO`) code.
+pub fn normalize_location_id(location_id: &str) -> Option<String> {
+    let (gazetteer, code) = location_id.trim().split_once(':')?;
+    let gazetteer = gazetteer.trim().to_lowercase();
+    let code = code.trim();
+    if gazetteer.is_empty() || code.is_empty() {
+        return None;
+    }
+    let code = match gazetteer.as_str() {
+        ISO_GAZETTEER => normalize_iso_location_code(code)?,
+        TDWG_GAZETTEER => normalize_tdwg_location_code(code)?,
+        _ => code.to_string(),
+    };
+    return Some(format!("{gazetteer}:{code}"));
+}
+
+/// Country of a normalized ISO location id, subdivisions like `iso:US-CA` give their country.
+pub fn location_id_country_code(location_id: &str) -> Option<String> {
+    let (gazetteer, code) = location_id.split_once(':')?;
+    if gazetteer != ISO_GAZETTEER {
+        return None;
+    }
+    let country_code = code.split('-').next()?;
+    return normalize_country_code(country_code);
+}
+
+fn normalize_iso_location_code(code: &str) -> Option<String> {
+    let (country_code, subdivision) = match code.split_once('-') {
+        Some((country_code, subdivision)) => (country_code, Some(subdivision)),
+        None => (code, None),
+    };
+    let country_code = normalize_country_code(country_code)?;
+    let Some(subdivision) = subdivision else {
+        return Some(country_code);
+    };
+    if subdivision.is_empty()
+        || subdivision.len() > 3
+        || !subdivision.chars().all(|x| x.is_ascii_alphanumeric())
+    {
+        return None;
+    }
+    return Some(format!(
+        "{country_code}-{}",
+        subdivision.to_ascii_uppercase()
+    ));
+}
+
+fn normalize_tdwg_location_code(code: &str) -> Option<String> {
+    let code = code.to_ascii_uppercase();
+    let is_letters =
+        |x: &str, len: usize| x.len() == len && x.chars().all(|x| x.is_ascii_uppercase());
+    let is_valid = match code.split_once('-') {
+        Some((level_3, level_4)) => is_letters(level_3, 3) && is_letters(level_4, 2),
+        None => {
+            (matches!(code.len(), 1 | 2)
+                && code.chars().all(|x| x.is_ascii_digit())
+                && !code.starts_with('0'))
+                || is_letters(&code, 3)
+        }
+    };
+    if !is_valid {
+        return None;
+    }
+    return Some(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso_country_codes_are_sorted() {
+        assert!(ISO_COUNTRY_CODES.is_sorted());
+    }
+
+    #[test]
+    fn normalizes_country_codes() {
+        assert_eq!(normalize_country_code(" de ").as_deref(), Some("DE"));
+        assert_eq!(normalize_country_code("UK").as_deref(), Some("GB"));
+        assert_eq!(normalize_country_code("XX"), None);
+        assert_eq!(normalize_country_code("EU"), None);
+        assert_eq!(normalize_country_code("GBR"), None);
+    }
+
+    #[test]
+    fn normalizes_location_ids() {
+        assert_eq!(
+            normalize_location_id("ISO:us-ca").as_deref(),
+            Some("iso:US-CA")
+        );
+        assert_eq!(normalize_location_id("iso:ZZ"), None);
+        assert_eq!(normalize_location_id("tdwg:1").as_deref(), Some("tdwg:1"));
+        assert_eq!(normalize_location_id("tdwg:12").as_deref(), Some("tdwg:12"));
+        assert_eq!(
+            normalize_location_id("tdwg:grb").as_deref(),
+            Some("tdwg:GRB")
+        );
+        assert_eq!(
+            normalize_location_id("tdwg:grb-oo").as_deref(),
+            Some("tdwg:GRB-OO")
+        );
+        assert_eq!(normalize_location_id("tdwg:123"), None);
+        assert_eq!(normalize_location_id("tdwg:GRB-O"), None);
+        assert_eq!(normalize_location_id("fao:27").as_deref(), Some("fao:27"));
+        assert_eq!(normalize_location_id("Europe"), None);
+        assert_eq!(location_id_country_code("iso:US-CA").as_deref(), Some("US"));
+    }
+}
@@ -1,242 +1,409 @@
+#![allow(clippy::needless_return)]
+
 use std::{
-    collections::{HashMap, HashSet},
     error::Error,
     fs::File,
-    io::{Read, Write},
+    io::{BufReader, BufWriter},
     time::Instant,
 };
 
-use crate::{addin_tsv_hashmaps::VernacularHashKey, json_types::*, tsv_types::*};
+use moth_filter::*;
+
+use crate::{
+    compressed_output::{ZstdOptions, write_with_zstd_copy},
+    moth_extraction::{MothExtraction, SpeciesSortOrder},
+};
 
 mod addin_tsv_hashmaps;
-mod json_types;
+mod compressed_output;
+mod csv_export;
+mod dwca_export;
+mod location_codes;
+#[cfg(feature = "sha2")]
+mod manifest;
+mod moth_extraction;
+#[cfg(feature = "parquet")]
+mod parquet_export;
+mod reconcile;
+mod repl;
+#[cfg(feature = "serve")]
+mod server;
+#[cfg(feature = "sqlite")]
+mod sqlite_export;
+mod taxon_lint;
 mod tsv_parsing;
 mod tsv_types;
 
-const MOTH_ORDER: &str = "Lepidoptera";
-const BUTTERFLY_SUPERFAMILY: &str = "Papilionoidea";
+const DATA_DIR: &str = "./data";
+const MOTH_DATA_PATH: &str = "./output/moth_data.json";
+const MOTH_SYNONYMS_PATH: &str = "./output/moth_synonyms.json";
+const BUTTERFLY_BLACKLIST_PATH: &str = "./output/butterfly_blacklist.json";
+#[cfg(feature = "serve")]
+const DEFAULT_SERVE_ADDRESS: &str = "127.0.0.1:8080";
+const USAGE: &str = "\
+Usage: moth_filter [subcommand]
+
+Subcommands:
+  generate (default) [--dwca] [--low-memory] [--sort classification|id|source]
+                     [--previous-output path] [--top-genera N]
+                     [--zstd-level N] [--zstd-long] [--zstd-threads N]
+                        Extract moth data from ./data into ./output, with --dwca also as a
+                        Darwin Core Archive in ./output/dwca, with --low-memory only loading
+                        side table rows of moths at the cost of reading Taxon.tsv twice.
+                        Species are sorted by classification unless --sort says otherwise.
+                        --previous-output maps the taxon ids of an earlier output to the new ones.
+                        --top-genera sets how many of the largest genera are listed in the stats
+  match <name> [--limit N] [--min-score S]
+                        Fuzzy match a scientific name against ./output/moth_data.json
+  reconcile <names.csv|names.tsv> [--output path] [--column name] [--no-fuzzy] [--min-score S]
+                        Resolve every name in a file to its accepted moth taxon
+  diff <old> <new> [--json]
+                        Report what changed between two releases, each a moth_data.json, an
+                        output directory or a raw data directory with Taxon.tsv
+  lint [path] [--json]  Report data quality issues in a raw data directory (./data by default),
+                        with Taxon.tsv only checks, or in a moth_data.json
+  repl                  Explore the output interactively, building it from ./data if missing
+  serve [--address host:port]
+                        Serve JSON lookups over HTTP (requires the `serve` feature)";
+const DEFAULT_MATCH_LIMIT: usize = 10;
 
 fn main() {
-    let start_time = Instant::now();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let subcommand = if args.is_empty() {
+        "generate".to_string()
+    } else {
+        args.remove(0)
+    };
+    match subcommand.as_str() {
+        "generate" => generate(args),
+        "match" => match_names(args),
+        "reconcile" => reconcile_names(args),
+        "diff" => diff_releases_command(args),
+        "lint" => lint(args),
+        "repl" => run_repl(),
+        "serve" => serve(args),
+        _ => {
+            eprintln!("Unknown subcommand `{subcommand}`");
+            print_usage();
+            std::process::exit(1);
+        }
+    }
+}
 
-    let mut taxon_tsv_reader = csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .quoting(false)
-        .from_reader(File::open("./data/Taxon.tsv").unwrap());
-    let taxon_tsv = taxon_tsv_reader.deserialize::<TaxonTSVRaw>();
+fn print_usage() {
+    eprintln!("{USAGE}");
+}
 
-    let tsv_maps = tsv_parsing::parse_tsvs();
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let flag_position = args.iter().position(|x| x == flag)?;
+    if flag_position + 1 >= args.len() {
+        eprintln!("Missing value for `{flag}`");
+        std::process::exit(1);
+    }
+    let value = args.remove(flag_position + 1);
+    args.remove(flag_position);
+    return Some(value);
+}
 
-    let mut bad_entry_count = 0;
-    let mut moth_entries: Vec<SpeciesData> = Vec::new();
-    let mut synonyms: HashMap<String, Vec<SynonymSpecies>> = HashMap::new();
-    let mut moth_ids: HashSet<String> = HashSet::new();
-    let mut butterfly_data: ButterflyBlacklist = Default::default();
+fn take_parsed_flag_value<T: std::str::FromStr>(args: &mut Vec<String>, flag: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = take_flag_value(args, flag)?;
+    return match value.parse::<T>() {
+        Ok(ok) => Some(ok),
+        Err(err) => {
+            eprintln!("Invalid value `{value}` for `{flag}`: {err}");
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+}
 
-    for tsv_reader_result in taxon_tsv {
-        let Ok(taxon_tsv_data_raw) = tsv_reader_result else {
-            bad_entry_count += 1;
-            continue;
-        };
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let Some(flag_position) = args.iter().position(|x| x == flag) else {
+        return false;
+    };
+    args.remove(flag_position);
+    return true;
+}
 
-        // filter out not species before checking for synonyms
-        if taxon_tsv_data_raw.dwc_taxon_rank != "species" {
-            continue;
+fn load_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    let json_file = File::open(path)?;
+    return Ok(serde_json::from_reader(BufReader::new(json_file))?);
+}
+
+fn load_or_exit<T: serde::de::DeserializeOwned>(path: &str) -> T {
+    return match load_json(path) {
+        Ok(ok) => ok,
+        Err(err) => {
+            eprintln!("Failed to load {path}: {err}");
+            std::process::exit(1);
         }
+    };
+}
 
-        // synonyms have nearly no data and will never be detected as a moth, run before moth check and filter out non moths later
-        match taxon_tsv_data_raw.dwc_taxonomic_status {
-            TaxonomicStatusRaw::Synonym | TaxonomicStatusRaw::AmbiguousSynonym => {
-                let primary_taxon_id = taxon_tsv_data_raw.dwc_accepted_name_usage_id;
-                if let Some(genus) = taxon_tsv_data_raw.dwc_generic_name
-                    && let Some(epithet) = taxon_tsv_data_raw.dwc_specific_epithet
-                {
-                    let synonym = SynonymSpecies {
-                        catalogue_of_life_taxon_id: taxon_tsv_data_raw.dwc_taxon_id,
-                        genus: genus,
-                        epithet: epithet,
-                    };
-                    synonyms
-                        .entry(primary_taxon_id)
-                        .and_modify(|x| {
-                            x.push(synonym.clone());
-                        })
-                        .or_insert(vec![synonym]);
-                }
-                continue;
-            }
-            TaxonomicStatusRaw::Misapplied => {
-                continue;
-            }
-            _ => (),
-        };
+fn load_moth_lookup() -> MothLookup {
+    return MothLookup::new(
+        load_or_exit(MOTH_DATA_PATH),
+        load_or_exit(MOTH_SYNONYMS_PATH),
+        load_or_exit(BUTTERFLY_BLACKLIST_PATH),
+    );
+}
 
-        // continue if not a moth
-        let Some(order) = &taxon_tsv_data_raw.dwc_order else {
-            continue;
-        };
-        if order != MOTH_ORDER {
-            continue;
+fn match_names(mut args: Vec<String>) {
+    let limit = take_parsed_flag_value(&mut args, "--limit").unwrap_or(DEFAULT_MATCH_LIMIT);
+    let min_score =
+        take_parsed_flag_value(&mut args, "--min-score").unwrap_or(DEFAULT_FUZZY_MIN_SCORE);
+    let query = args.join(" ");
+    if query.is_empty() {
+        print_usage();
+        std::process::exit(1);
+    }
+
+    let moth_data: MothDataJson = load_or_exit(MOTH_DATA_PATH);
+    let matcher = FuzzyMatcher::new(&moth_data);
+    let matches = matcher.find_matches(&query, min_score, limit);
+    if matches.is_empty() {
+        println!("No matches found for \"{query}\"");
+    }
+    for fuzzy_match in matches {
+        if fuzzy_match.synonym {
+            println!(
+                "{:.3}  {} (synonym of {}, {})",
+                fuzzy_match.score,
+                fuzzy_match.matched_name,
+                fuzzy_match.accepted_name,
+                fuzzy_match.catalogue_of_life_taxon_id
+            );
+        } else {
+            println!(
+                "{:.3}  {} ({})",
+                fuzzy_match.score, fuzzy_match.matched_name, fuzzy_match.catalogue_of_life_taxon_id
+            );
         }
-        if let Some(superfamily) = &taxon_tsv_data_raw.dwc_superfamily
-            && superfamily == BUTTERFLY_SUPERFAMILY
-        {
-            if let Some(family) = taxon_tsv_data_raw.dwc_family {
-                butterfly_data.families.insert(family.to_lowercase());
-            }
-            if let Some(subfamily) = taxon_tsv_data_raw.dwc_subfamily {
-                butterfly_data.subfamilies.insert(subfamily.to_lowercase());
-            }
-            if let Some(tribe) = taxon_tsv_data_raw.dwc_tribe {
-                butterfly_data.tribes.insert(tribe.to_lowercase());
-            }
-            if let Some(subtribe) = taxon_tsv_data_raw.dwc_subtribe {
-                butterfly_data.subtribes.insert(subtribe.to_lowercase());
-            }
-            if let Some(genus) = taxon_tsv_data_raw.dwc_genus {
-                butterfly_data.genera.insert(genus.to_lowercase());
-            }
-            if let Some(genus) = taxon_tsv_data_raw.dwc_generic_name {
-                butterfly_data.genera.insert(genus.to_lowercase());
-            }
-            if let Some(epithet) = taxon_tsv_data_raw.dwc_specific_epithet {
-                butterfly_data.epithets.insert(epithet.to_lowercase());
-            }
-            continue;
+    }
+}
+
+fn reconcile_names(mut args: Vec<String>) {
+    let output_path = take_flag_value(&mut args, "--output");
+    let name_column = take_flag_value(&mut args, "--column");
+    let min_score =
+        take_parsed_flag_value(&mut args, "--min-score").unwrap_or(DEFAULT_FUZZY_MIN_SCORE);
+    let fuzzy_min_score = if take_flag(&mut args, "--no-fuzzy") {
+        None
+    } else {
+        Some(min_score)
+    };
+    let Some(input_path) = args.first() else {
+        print_usage();
+        std::process::exit(1);
+    };
+    let output_path = output_path.unwrap_or_else(|| reconcile::default_output_path(input_path));
+
+    let moth_lookup = load_moth_lookup();
+    let reconcile_start_time = Instant::now();
+    match reconcile::reconcile_file(
+        input_path,
+        &output_path,
+        name_column.as_deref(),
+        &moth_lookup,
+        fuzzy_min_score,
+    ) {
+        Ok(counts) => {
+            println!(
+                "Reconciled {} names: {} accepted, {} synonyms, {} fuzzy, {} unmatched",
+                counts.accepted + counts.synonym + counts.fuzzy + counts.no_match,
+                counts.accepted,
+                counts.synonym,
+                counts.fuzzy,
+                counts.no_match,
+            );
+            println!(
+                "Wrote {} in: {:.6?}",
+                output_path,
+                reconcile_start_time.elapsed()
+            );
+        }
+        Err(err) => {
+            eprintln!("Failed to reconcile {input_path}: {err}");
+            std::process::exit(1);
         }
+    }
+}
 
-        moth_ids.insert(taxon_tsv_data_raw.dwc_taxon_id.clone());
-
-        let common_name = tsv_maps.vernacular_name.get(&VernacularHashKey {
-            language_code: "eng".to_string(),
-            taxon_id: taxon_tsv_data_raw.dwc_taxon_id.clone(),
-        });
-        let species_profile = tsv_maps
-            .species_profile
-            .get(&taxon_tsv_data_raw.dwc_taxon_id)
-            .and_then(|x| {
-                Some(SpeciesProfile {
-                    extinct: x.gbif_is_extinct,
-                    freshwater: x.gbif_is_freshwater,
-                    marine: x.gbif_is_marine,
-                })
-            });
-
-        let distribution = tsv_maps
-            .distribution
-            .get(&taxon_tsv_data_raw.dwc_taxon_id)
-            .and_then(|x| {
-                let threat_status = x.iucn_threat_status.as_ref().and_then(|x| match x {
-                    ThreatStatusRaw::LeastConcern => Some(ThreatStatus::LeastConcern),
-                    ThreatStatusRaw::Vulnerable => Some(ThreatStatus::Vulnerable),
-                    ThreatStatusRaw::Endangered => Some(ThreatStatus::Endangered),
-                    ThreatStatusRaw::CriticallyEndangered => {
-                        Some(ThreatStatus::CriticallyEndangered)
-                    }
-                    ThreatStatusRaw::ExtinctInTheWild => Some(ThreatStatus::ExtinctInTheWild),
-                    ThreatStatusRaw::Extinct => Some(ThreatStatus::Extinct),
-                    ThreatStatusRaw::NotEvaluated => None,
-                    ThreatStatusRaw::DataDeficient => None,
-                });
-                if x.dwc_locality.is_none() && threat_status.is_none() {
-                    return None;
-                }
-                Some(Distribution {
-                    locality: x.dwc_locality.clone(),
-                    threat_status,
-                })
-            });
-
-        // some malformed entries dont have a `genus` but have a `generic name` which is synonymous
-        let genus_fixed = match taxon_tsv_data_raw.dwc_genus {
-            Some(some) => some,
-            None => match taxon_tsv_data_raw.dwc_generic_name {
-                Some(some) => some,
-                None => {
-                    bad_entry_count += 1;
-                    continue;
-                }
-            },
-        };
+fn diff_releases_command(mut args: Vec<String>) {
+    let json_output = take_flag(&mut args, "--json");
+    let [old_path, new_path] = args.as_slice() else {
+        print_usage();
+        std::process::exit(1);
+    };
 
-        let Some(epithet_checked) = taxon_tsv_data_raw.dwc_specific_epithet else {
-            bad_entry_count += 1;
-            continue;
+    let (old_moth_data, old_butterfly_blacklist) = load_release(old_path);
+    let (new_moth_data, new_butterfly_blacklist) = load_release(new_path);
+    let release_diff = diff_releases(
+        &old_moth_data,
+        &new_moth_data,
+        old_butterfly_blacklist.as_ref(),
+        new_butterfly_blacklist.as_ref(),
+    );
+
+    if json_output {
+        if let Err(err) = serde_json::to_writer_pretty(std::io::stdout(), &release_diff) {
+            eprintln!("{err}");
         };
+        println!();
+    } else {
+        print!("{release_diff}");
+    }
+}
 
-        moth_entries.push(SpeciesData {
-            catalogue_of_life_taxon_id: taxon_tsv_data_raw.dwc_taxon_id,
-            classification: ScientificClassification {
-                superfamily: taxon_tsv_data_raw.dwc_superfamily,
-                family: taxon_tsv_data_raw.dwc_family,
-                subfamily: taxon_tsv_data_raw.dwc_subfamily,
-                tribe: taxon_tsv_data_raw.dwc_tribe,
-                subtribe: taxon_tsv_data_raw.dwc_subtribe,
-                genus: genus_fixed,
-                epithet: epithet_checked,
-            },
-            common_names: common_name.cloned(),
-            species_profile: species_profile,
-            distribution: distribution,
-            synonyms: None,
-            published_in: taxon_tsv_data_raw.dwc_name_published_in,
-        });
+// a `moth_data.json`, an output directory containing one or a raw data directory with `Taxon.tsv`
+// the butterfly blacklist is taken from next to `moth_data.json` if it is there
+fn load_release(path: &str) -> (MothDataJson, Option<ButterflyBlacklist>) {
+    let path = std::path::Path::new(path);
+    if path.join("Taxon.tsv").exists() {
+        let moth_extraction = moth_extraction::extract_moth_data(&path.to_string_lossy(), false);
+        return (
+            moth_extraction.moth_entries,
+            Some(moth_extraction.butterfly_blacklist),
+        );
     }
 
-    synonyms.retain(|key, _value| moth_ids.contains(key));
-    let moth_synonyms_count: usize = synonyms.iter().map(|x| x.1.len()).sum();
+    let moth_data_path = if path.is_dir() {
+        path.join("moth_data.json")
+    } else {
+        path.to_path_buf()
+    };
+    let butterfly_blacklist_path = moth_data_path.with_file_name("butterfly_blacklist.json");
+    let moth_data = load_or_exit(&moth_data_path.to_string_lossy());
+    let butterfly_blacklist = if butterfly_blacklist_path.exists() {
+        Some(load_or_exit(&butterfly_blacklist_path.to_string_lossy()))
+    } else {
+        None
+    };
+    return (moth_data, butterfly_blacklist);
+}
 
-    let mut butterfly_collision_data: ButterflyBlacklist = Default::default();
+fn lint(mut args: Vec<String>) {
+    let json_output = take_flag(&mut args, "--json");
+    let path = args.first().map_or(DATA_DIR, |x| x.as_str());
 
-    for moth_entry in moth_entries.iter_mut() {
-        // eliminate any false positives in butterfly blacklist
-        // only genera and epithets appear to collide but check over all of them anyways
-        if let Some(family) = &moth_entry.classification.family {
-            if butterfly_data.families.remove(&family.to_lowercase()) {
-                butterfly_collision_data.families.insert(family.clone());
-            }
-        }
-        if let Some(subfamily) = &moth_entry.classification.subfamily {
-            if butterfly_data.subfamilies.remove(&subfamily.to_lowercase()) {
-                butterfly_collision_data
-                    .subfamilies
-                    .insert(subfamily.clone());
-            }
-        }
-        if let Some(tribe) = &moth_entry.classification.tribe {
-            if butterfly_data.tribes.remove(&tribe.to_lowercase()) {
-                butterfly_collision_data.tribes.insert(tribe.clone());
-            }
-        }
-        if let Some(subtribe) = &moth_entry.classification.subtribe {
-            if butterfly_data.subtribes.remove(&subtribe.to_lowercase()) {
-                butterfly_collision_data.subtribes.insert(subtribe.clone());
+    let (moth_data, _) = load_release(path);
+    let mut lint_issues = lint_moth_data(&moth_data);
+    if std::path::Path::new(path).join("Taxon.tsv").exists() {
+        lint_issues.extend(taxon_lint::lint_taxon_rows(path, &moth_data));
+    }
+    lint_issues.sort_by_key(|x| x.kind);
+
+    if json_output {
+        if let Err(err) = serde_json::to_writer_pretty(std::io::stdout(), &lint_issues) {
+            eprintln!("{err}");
+        };
+        println!();
+        return;
+    }
+
+    let lint_issue_counts = count_lint_issues(&lint_issues);
+    for (kind, count) in &lint_issue_counts {
+        println!("{kind} ({count}):");
+        for lint_issue in lint_issues.iter().filter(|x| x.kind == *kind) {
+            match &lint_issue.detail {
+                Some(detail) => println!(
+                    "  {} ({}): {}",
+                    lint_issue.name, lint_issue.catalogue_of_life_taxon_id, detail
+                ),
+                None => println!(
+                    "  {} ({})",
+                    lint_issue.name, lint_issue.catalogue_of_life_taxon_id
+                ),
             }
         }
-        if butterfly_data
-            .genera
-            .remove(&moth_entry.classification.genus.to_lowercase())
-        {
-            butterfly_collision_data
-                .genera
-                .insert(moth_entry.classification.genus.clone());
-        }
-        if butterfly_data
-            .epithets
-            .remove(&moth_entry.classification.epithet.to_lowercase())
-        {
-            butterfly_collision_data
-                .epithets
-                .insert(moth_entry.classification.epithet.clone());
-        }
+    }
+    println!(
+        "Found {} issues in {} species",
+        lint_issues.len(),
+        moth_data.len()
+    );
+}
 
-        // append synonyms
-        moth_entry.synonyms = synonyms
-            .get(&moth_entry.catalogue_of_life_taxon_id)
-            .cloned();
+fn run_repl() {
+    let outputs_exist = [MOTH_DATA_PATH, MOTH_SYNONYMS_PATH, BUTTERFLY_BLACKLIST_PATH]
+        .iter()
+        .all(|x| std::path::Path::new(x).exists());
+    let moth_lookup = if outputs_exist {
+        load_moth_lookup()
+    } else {
+        println!("No output found, building moth data from ./data");
+        let moth_extraction = moth_extraction::extract_moth_data(DATA_DIR, false);
+        MothLookup::new(
+            moth_extraction.moth_entries,
+            moth_extraction.moth_synonyms,
+            moth_extraction.butterfly_blacklist,
+        )
+    };
+    repl::run_repl(&moth_lookup);
+}
+
+#[cfg(feature = "serve")]
+fn serve(mut args: Vec<String>) {
+    let address =
+        take_flag_value(&mut args, "--address").unwrap_or(DEFAULT_SERVE_ADDRESS.to_string());
+    let moth_lookup = load_moth_lookup();
+    if let Err(err) = server::serve(&address, &moth_lookup) {
+        eprintln!("Failed to serve on {address}: {err}");
+        std::process::exit(1);
     }
+}
 
-    let reversed_synonyms_data = get_reversed_synonym_map(&synonyms);
+#[cfg(not(feature = "serve"))]
+fn serve(_args: Vec<String>) {
+    eprintln!("moth_filter was built without the `serve` feature, rebuild with `--features serve`");
+    std::process::exit(1);
+}
+
+fn generate(mut args: Vec<String>) {
+    let write_dwca = take_flag(&mut args, "--dwca");
+    let low_memory = take_flag(&mut args, "--low-memory");
+    let previous_output_path = take_flag_value(&mut args, "--previous-output");
+    let top_genera =
+        take_parsed_flag_value(&mut args, "--top-genera").unwrap_or(DEFAULT_TOP_GENERA);
+    // loaded up front since the previous output may be the one about to be overwritten
+    let previous_moth_entries = previous_output_path.as_ref().map(|x| load_release(x).0);
+    let sort_order = take_flag_value(&mut args, "--sort")
+        .map(|x| {
+            x.parse::<SpeciesSortOrder>().unwrap_or_else(|err| {
+                eprintln!("{err}");
+                print_usage();
+                std::process::exit(1);
+            })
+        })
+        .unwrap_or(SpeciesSortOrder::Classification);
+    let default_zstd_options: ZstdOptions = Default::default();
+    let zstd_options = ZstdOptions {
+        level: take_parsed_flag_value(&mut args, "--zstd-level")
+            .unwrap_or(default_zstd_options.level),
+        long_distance_matching: take_flag(&mut args, "--zstd-long"),
+        threads: take_parsed_flag_value(&mut args, "--zstd-threads")
+            .unwrap_or(default_zstd_options.threads),
+    };
+    let start_time = Instant::now();
+
+    let MothExtraction {
+        mut moth_entries,
+        moth_synonyms: reversed_synonyms_data,
+        moth_synonyms_count,
+        butterfly_blacklist: butterfly_data,
+        butterfly_collisions: butterfly_collision_data,
+        bad_entry_count,
+        phase_timings,
+    } = moth_extraction::extract_moth_data(DATA_DIR, low_memory);
+
+    moth_extraction::sort_moth_entries(&mut moth_entries, sort_order);
+
+    for (phase, duration) in phase_timings {
+        println!("{phase} in: {duration:.6?}");
+    }
 
     println!(
         "Found {} moths and {} synonym species",
@@ -246,67 +413,103 @@ fn main() {
     if bad_entry_count > 0 {
         println!("Failed to parse {bad_entry_count} entries");
     }
+    let genus_mismatch_count = moth_entries
+        .iter()
+        .filter(|x| x.classification.has_genus_mismatch())
+        .count();
+    if genus_mismatch_count > 0 {
+        println!(
+            "Found {genus_mismatch_count} species whose genus disagrees with their generic name"
+        );
+    }
     println!(
         "Parsed and constructed data in: {:.6?}",
         start_time.elapsed()
     );
 
+    // checksummed in the manifest, stale files from earlier runs in ./output are not outputs of this one
+    let mut written_output_paths: Vec<String> = Vec::new();
+
     let moth_output_file_path = "./output/moth_data.json";
-    let moth_output_file_path_zstd = moth_output_file_path.to_owned() + ".zst";
-    let moth_output_file = File::create(moth_output_file_path).unwrap();
-    let moth_output_file_zstd = File::create(&moth_output_file_path_zstd).unwrap();
-    println!("Writing moth data output to {}", moth_output_file_path);
-    if let Err(write_error) = serde_json::to_writer_pretty(moth_output_file, &moth_entries) {
-        dbg!(write_error);
-    };
     println!(
-        "Writing compressed moth data output to {}",
-        moth_output_file_path_zstd
+        "Writing moth data output to {} and {}.zst",
+        moth_output_file_path, moth_output_file_path
     );
-    if let Err(err) = write_zstd(moth_output_file_path, &moth_output_file_zstd) {
-        eprintln!("{err}");
+    match write_with_zstd_copy(moth_output_file_path, &zstd_options, |x| {
+        Ok(serde_json::to_writer_pretty(x, &moth_entries)?)
+    }) {
+        Ok(()) => written_output_paths.extend([
+            moth_output_file_path.to_string(),
+            format!("{moth_output_file_path}.zst"),
+        ]),
+        Err(err) => eprintln!("{err}"),
     };
 
-    let moth_synonyms_output_file_path = "./output/moth_synonyms.json";
-    let moth_synonyms_output_file_path_zstd = moth_synonyms_output_file_path.to_owned() + ".zst";
-    let moth_synonyms_output_file = File::create(moth_synonyms_output_file_path).unwrap();
-    let moth_synonyms_output_file_zstd =
-        File::create(&moth_synonyms_output_file_path_zstd).unwrap();
+    let moth_dictionary_output_file_path = "./output/moth_data.zdict";
+    let moth_records_output_file_path = "./output/moth_data.records.zst";
+    let moth_records_index_output_file_path = "./output/moth_data.records.index.json";
     println!(
-        "Writing moth synonyms output to {}",
-        moth_synonyms_output_file_path
+        "Writing dictionary compressed moth data records output to {} with dictionary {} and index {}",
+        moth_records_output_file_path,
+        moth_dictionary_output_file_path,
+        moth_records_index_output_file_path
     );
-    if let Err(write_error) = serde_json::to_writer_pretty(moth_synonyms_output_file, &reversed_synonyms_data) {
-        dbg!(write_error);
+    match write_dictionary_compressed_records(
+        moth_dictionary_output_file_path,
+        moth_records_output_file_path,
+        moth_records_index_output_file_path,
+        &moth_entries,
+    ) {
+        Ok(()) => written_output_paths.extend([
+            moth_dictionary_output_file_path.to_string(),
+            moth_records_output_file_path.to_string(),
+            moth_records_index_output_file_path.to_string(),
+        ]),
+        Err(err) => eprintln!("{err}"),
     };
+
+    let moth_ndjson_output_file_path = "./output/moth_data.jsonl";
+    let moth_ndjson_output_file = File::create(moth_ndjson_output_file_path).unwrap();
     println!(
-        "Writing compressed moth synonyms output to {}",
-        moth_synonyms_output_file_path_zstd
+        "Writing moth data json lines output to {}",
+        moth_ndjson_output_file_path
     );
-    if let Err(err) = write_zstd(
-        moth_synonyms_output_file_path,
-        &moth_synonyms_output_file_zstd,
-    ) {
-        eprintln!("{err}");
+    match write_moth_data_ndjson(BufWriter::new(moth_ndjson_output_file), &moth_entries) {
+        Ok(()) => written_output_paths.push(moth_ndjson_output_file_path.to_string()),
+        Err(err) => eprintln!("{err}"),
     };
 
-    let butterfly_output_file_path = "./output/butterfly_blacklist.json";
-    let butterfly_output_file_path_zstd = butterfly_output_file_path.to_owned() + ".zst";
-    let butterfly_output_file = File::create(butterfly_output_file_path).unwrap();
-    let butterfly_output_file_zstd = File::create(&butterfly_output_file_path_zstd).unwrap();
+    let moth_synonyms_output_file_path = "./output/moth_synonyms.json";
     println!(
-        "Writing butterfly blacklist output to {}",
-        butterfly_output_file_path
+        "Writing moth synonyms output to {} and {}.zst",
+        moth_synonyms_output_file_path, moth_synonyms_output_file_path
     );
-    if let Err(write_error) = serde_json::to_writer_pretty(butterfly_output_file, &butterfly_data) {
-        dbg!(write_error);
+    match write_with_zstd_copy(moth_synonyms_output_file_path, &zstd_options, |x| {
+        Ok(serde_json::to_writer_pretty(
+            x,
+            &sorted_moth_synonyms(&reversed_synonyms_data),
+        )?)
+    }) {
+        Ok(()) => written_output_paths.extend([
+            moth_synonyms_output_file_path.to_string(),
+            format!("{moth_synonyms_output_file_path}.zst"),
+        ]),
+        Err(err) => eprintln!("{err}"),
     };
+
+    let butterfly_output_file_path = "./output/butterfly_blacklist.json";
     println!(
-        "Writing compressed butterfly blacklist output to {}",
-        butterfly_output_file_path_zstd
+        "Writing butterfly blacklist output to {} and {}.zst",
+        butterfly_output_file_path, butterfly_output_file_path
     );
-    if let Err(err) = write_zstd(butterfly_output_file_path, &butterfly_output_file_zstd) {
-        eprintln!("{err}");
+    match write_with_zstd_copy(butterfly_output_file_path, &zstd_options, |x| {
+        Ok(serde_json::to_writer_pretty(x, &butterfly_data)?)
+    }) {
+        Ok(()) => written_output_paths.extend([
+            butterfly_output_file_path.to_string(),
+            format!("{butterfly_output_file_path}.zst"),
+        ]),
+        Err(err) => eprintln!("{err}"),
     };
 
     let butterfly_collisions_output_file_path = "./output/butterfly_blacklist_collisions.json";
@@ -316,30 +519,249 @@ fn main() {
         "Writing butterfly blacklist collisions output to {}",
         butterfly_collisions_output_file_path
     );
-    if let Err(write_error) =
-        serde_json::to_writer_pretty(butterfly_collisions_output_file, &butterfly_collision_data)
+    match serde_json::to_writer_pretty(butterfly_collisions_output_file, &butterfly_collision_data)
     {
-        dbg!(write_error);
+        Ok(()) => written_output_paths.push(butterfly_collisions_output_file_path.to_string()),
+        Err(err) => eprintln!("{err}"),
     };
-}
 
-fn get_reversed_synonym_map(synonyms: &HashMap<String, Vec<SynonymSpecies>>) -> MothSynonyms {
-    let mut new_synonyms: HashMap<String, String> = Default::default();
-    for (taxon_id, synonyms_data) in synonyms.iter() {
-        for synonym_data in synonyms_data {
-            new_synonyms.insert(format!("{} {}", synonym_data.genus, synonym_data.epithet), taxon_id.to_string());
+    if let Some(previous_moth_entries) = previous_moth_entries {
+        let id_mappings = map_taxon_ids(&previous_moth_entries, &moth_entries);
+        let id_mapping_output_file_path = "./output/id_mapping.json";
+        println!(
+            "Writing id mapping from {} to {}",
+            previous_output_path.unwrap_or_default(),
+            id_mapping_output_file_path
+        );
+        for reason in [
+            IdMappingReason::Unchanged,
+            IdMappingReason::NameMatch,
+            IdMappingReason::Synonymized,
+            IdMappingReason::Split,
+            IdMappingReason::Deleted,
+        ] {
+            let reason_count = id_mappings.iter().filter(|x| x.reason == reason).count();
+            println!("{reason:?}: {reason_count}");
         }
+        let id_mapping_output_file = File::create(id_mapping_output_file_path).unwrap();
+        match serde_json::to_writer_pretty(BufWriter::new(id_mapping_output_file), &id_mappings) {
+            Ok(()) => written_output_paths.push(id_mapping_output_file_path.to_string()),
+            Err(err) => eprintln!("{err}"),
+        };
     }
-    return new_synonyms;
-}
 
-fn write_zstd(input_file_path: &str, mut output_file: &File) -> Result<(), Box<dyn Error>> {
-    let mut compression_target_data = Vec::new();
-    File::open(input_file_path)?.read_to_end(&mut compression_target_data)?;
+    #[cfg(feature = "msgpack")]
+    {
+        write_binary_output(
+            "./output/moth_data.msgpack",
+            "moth data",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_msgpack(x, &moth_entries),
+        );
+        write_binary_output(
+            "./output/moth_synonyms.msgpack",
+            "moth synonyms",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_msgpack(x, &sorted_moth_synonyms(&reversed_synonyms_data)),
+        );
+        write_binary_output(
+            "./output/butterfly_blacklist.msgpack",
+            "butterfly blacklist",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_msgpack(x, &butterfly_data),
+        );
+    }
 
-    let max_compression_level = *zstd::compression_level_range().end();
-    let mut compressor = zstd::bulk::Compressor::new(max_compression_level)?;
-    output_file.write(&compressor.compress(&compression_target_data)?)?;
+    #[cfg(feature = "cbor")]
+    {
+        write_binary_output(
+            "./output/moth_data.cbor",
+            "moth data",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_cbor(x, &moth_entries),
+        );
+        write_binary_output(
+            "./output/moth_synonyms.cbor",
+            "moth synonyms",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_cbor(x, &sorted_moth_synonyms(&reversed_synonyms_data)),
+        );
+        write_binary_output(
+            "./output/butterfly_blacklist.cbor",
+            "butterfly blacklist",
+            &zstd_options,
+            &mut written_output_paths,
+            |x| write_cbor(x, &butterfly_data),
+        );
+    }
 
-    return Ok(());
+    for (extension, delimiter) in [("csv", b','), ("tsv", b'\t')] {
+        let moth_table_output_file_path = format!("./output/moth_data.{extension}");
+        println!(
+            "Writing moth data {} output to {}",
+            extension, moth_table_output_file_path
+        );
+        match csv_export::write_moth_data(&moth_table_output_file_path, delimiter, &moth_entries) {
+            Ok(()) => written_output_paths.push(moth_table_output_file_path),
+            Err(err) => eprintln!("{err}"),
+        };
+
+        let moth_synonyms_table_output_file_path = format!("./output/moth_synonyms.{extension}");
+        println!(
+            "Writing moth synonyms {} output to {}",
+            extension, moth_synonyms_table_output_file_path
+        );
+        match csv_export::write_moth_synonyms(
+            &moth_synonyms_table_output_file_path,
+            delimiter,
+            &moth_entries,
+        ) {
+            Ok(()) => written_output_paths.push(moth_synonyms_table_output_file_path),
+            Err(err) => eprintln!("{err}"),
+        };
+    }
+
+    #[cfg(feature = "parquet")]
+    {
+        let moth_parquet_output_file_path = "./output/moth_data.parquet";
+        println!(
+            "Writing moth data parquet output to {}",
+            moth_parquet_output_file_path
+        );
+        match parquet_export::write_moth_data_parquet(moth_parquet_output_file_path, &moth_entries)
+        {
+            Ok(()) => written_output_paths.push(moth_parquet_output_file_path.to_string()),
+            Err(err) => eprintln!("{err}"),
+        };
+
+        let moth_synonyms_parquet_output_file_path = "./output/moth_synonyms.parquet";
+        println!(
+            "Writing moth synonyms parquet output to {}",
+            moth_synonyms_parquet_output_file_path
+        );
+        match parquet_export::write_moth_synonyms_parquet(
+            moth_synonyms_parquet_output_file_path,
+            &moth_entries,
+        ) {
+            Ok(()) => written_output_paths.push(moth_synonyms_parquet_output_file_path.to_string()),
+            Err(err) => eprintln!("{err}"),
+        };
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        let sqlite_output_file_path = "./output/moth_data.sqlite";
+        println!("Writing sqlite output to {}", sqlite_output_file_path);
+        match sqlite_export::write_sqlite(
+            sqlite_output_file_path,
+            &moth_entries,
+            &butterfly_data,
+            &butterfly_collision_data,
+        ) {
+            Ok(()) => written_output_paths.push(sqlite_output_file_path.to_string()),
+            Err(err) => eprintln!("{err}"),
+        };
+    }
+
+    if write_dwca {
+        let dwca_output_dir = "./output/dwca";
+        println!("Writing darwin core archive output to {}", dwca_output_dir);
+        match dwca_export::write_dwca(DATA_DIR, dwca_output_dir, &moth_entries) {
+            Ok(counts) => {
+                println!(
+                    "Wrote {} taxon rows and {} extension rows",
+                    counts.taxon_rows, counts.extension_rows
+                );
+                written_output_paths.extend(counts.file_paths);
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    for (description, index_output_file_path, index) in [
+        (
+            "country index",
+            "./output/country_index.json",
+            country_index(&moth_entries),
+        ),
+        (
+            "location index",
+            "./output/location_index.json",
+            location_index(&moth_entries),
+        ),
+    ] {
+        println!(
+            "Writing {} output to {} ({} entries)",
+            description,
+            index_output_file_path,
+            index.len()
+        );
+        let index_output_file = File::create(index_output_file_path).unwrap();
+        match serde_json::to_writer_pretty(BufWriter::new(index_output_file), &index) {
+            Ok(()) => written_output_paths.push(index_output_file_path.to_string()),
+            Err(err) => eprintln!("{err}"),
+        };
+    }
+
+    let moth_stats = MothStats::new(&moth_entries, top_genera);
+    let stats_output_file_path = "./output/stats.json";
+    let stats_markdown_output_file_path = "./output/stats.md";
+    println!(
+        "Writing stats output to {} and {}",
+        stats_output_file_path, stats_markdown_output_file_path
+    );
+    let stats_output_file = File::create(stats_output_file_path).unwrap();
+    match serde_json::to_writer_pretty(BufWriter::new(stats_output_file), &moth_stats) {
+        Ok(()) => written_output_paths.push(stats_output_file_path.to_string()),
+        Err(err) => eprintln!("{err}"),
+    };
+    match std::fs::write(stats_markdown_output_file_path, moth_stats.to_markdown()) {
+        Ok(()) => written_output_paths.push(stats_markdown_output_file_path.to_string()),
+        Err(err) => eprintln!("{err}"),
+    };
+
+    #[cfg(feature = "sha2")]
+    {
+        let manifest_output_file_path = "./output/manifest.json";
+        println!("Writing manifest output to {}", manifest_output_file_path);
+        if let Err(err) = manifest::write_manifest(
+            manifest_output_file_path,
+            DATA_DIR,
+            &written_output_paths,
+            manifest::ManifestCounts {
+                moth_entries: moth_entries.len(),
+                moth_synonyms: moth_synonyms_count,
+                bad_entries: bad_entry_count,
+            },
+            manifest::FilterConfig::new(sort_order),
+        ) {
+            eprintln!("{err}");
+        };
+    }
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn write_binary_output(
+    output_file_path: &str,
+    description: &str,
+    zstd_options: &ZstdOptions,
+    written_output_paths: &mut Vec<String>,
+    write: impl FnOnce(&mut dyn std::io::Write) -> Result<(), Box<dyn Error>>,
+) {
+    println!(
+        "Writing {} output to {} and {}.zst",
+        description, output_file_path, output_file_path
+    );
+    match write_with_zstd_copy(output_file_path, zstd_options, write) {
+        Ok(()) => written_output_paths.extend([
+            output_file_path.to_string(),
+            format!("{output_file_path}.zst"),
+        ]),
+        Err(err) => eprintln!("{err}"),
+    };
 }
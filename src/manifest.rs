@@ -0,0 +1,172 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Read},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{dwca_export, moth_extraction};
+
+const HASH_BUFFER_SIZE: usize = 1 << 20;
+
+#[derive(Serialize)]
+pub struct Manifest {
+    pub moth_filter_version: &'static str,
+    pub dataset: DatasetMetadata,
+    pub inputs: Vec<FileChecksum>,
+    pub outputs: Vec<FileChecksum>,
+    pub counts: ManifestCounts,
+    pub filter: FilterConfig,
+}
+
+/// Source release as described by its `metadata.yaml`, falling back to `eml.xml`.
+#[derive(Serialize, Default)]
+pub struct DatasetMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doi: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct FileChecksum {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Serialize)]
+pub struct ManifestCounts {
+    pub moth_entries: usize,
+    pub moth_synonyms: usize,
+    pub bad_entries: usize,
+}
+
+#[derive(Serialize)]
+pub struct FilterConfig {
+    pub order: &'static str,
+    pub excluded_superfamily: &'static str,
+    pub taxon_rank: &'static str,
+    pub common_name_language: &'static str,
+    pub sort: String,
+}
+
+impl FilterConfig {
+    pub fn new(sort_order: moth_extraction::SpeciesSortOrder) -> FilterConfig {
+        return FilterConfig {
+            order: moth_extraction::MOTH_ORDER,
+            excluded_superfamily: moth_extraction::BUTTERFLY_SUPERFAMILY,
+            taxon_rank: moth_extraction::TAXON_RANK,
+            common_name_language: moth_extraction::COMMON_NAME_LANGUAGE,
+            sort: sort_order.to_string(),
+        };
+    }
+}
+
+/// Writes `manifest_file_path` describing the run, checksumming everything in `data_dir` and the `output_file_paths` written by it.
+pub fn write_manifest(
+    manifest_file_path: &str,
+    data_dir: &str,
+    output_file_paths: &[String],
+    counts: ManifestCounts,
+    filter: FilterConfig,
+) -> Result<(), Box<dyn Error>> {
+    let manifest = Manifest {
+        moth_filter_version: env!("CARGO_PKG_VERSION"),
+        dataset: read_dataset_metadata(data_dir),
+        inputs: checksum_dir(data_dir)?,
+        outputs: checksum_files(output_file_paths.iter().map(PathBuf::from).collect())?,
+        counts,
+        filter,
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(manifest_file_path)?), &manifest)?;
+    return Ok(());
+}
+
+fn read_dataset_metadata(data_dir: &str) -> DatasetMetadata {
+    let mut dataset_metadata: DatasetMetadata = Default::default();
+    if let Ok(metadata_yaml) = std::fs::read_to_string(format!("{data_dir}/metadata.yaml")) {
+        // only top level `key: value` lines are needed, nested lists of contributors etc. are skipped
+        for line in metadata_yaml.lines() {
+            if line.starts_with([' ', '\t', '-', '#']) {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_matches(['"', '\'']).to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim() {
+                "title" => dataset_metadata.title = Some(value),
+                "version" => dataset_metadata.version = Some(value),
+                "issued" => dataset_metadata.issued = Some(value),
+                "doi" => dataset_metadata.doi = Some(value),
+                _ => (),
+            }
+        }
+    }
+
+    if dataset_metadata.title.is_none() {
+        dataset_metadata.title = dwca_export::read_eml_element(data_dir, "title");
+    }
+    if dataset_metadata.issued.is_none() {
+        dataset_metadata.issued = dwca_export::read_eml_element(data_dir, "pubDate");
+    }
+    return dataset_metadata;
+}
+
+fn checksum_dir(dir: &str) -> Result<Vec<FileChecksum>, Box<dyn Error>> {
+    let mut file_paths: Vec<PathBuf> = Vec::new();
+    let mut dirs: Vec<PathBuf> = vec![PathBuf::from(dir)];
+    while let Some(dir) = dirs.pop() {
+        for dir_entry in std::fs::read_dir(dir)? {
+            let path = dir_entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                file_paths.push(path);
+            }
+        }
+    }
+    return checksum_files(file_paths);
+}
+
+// sorted by path
+fn checksum_files(mut file_paths: Vec<PathBuf>) -> Result<Vec<FileChecksum>, Box<dyn Error>> {
+    file_paths.sort();
+
+    let mut file_checksums: Vec<FileChecksum> = Vec::with_capacity(file_paths.len());
+    for file_path in file_paths {
+        let mut file = File::open(&file_path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+        let mut size = 0;
+        loop {
+            let read_size = file.read(&mut buffer)?;
+            if read_size == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read_size]);
+            size += read_size as u64;
+        }
+        file_checksums.push(FileChecksum {
+            path: file_path.to_string_lossy().to_string(),
+            size,
+            sha256: hasher
+                .finalize()
+                .iter()
+                .map(|x| format!("{x:02x}"))
+                .collect(),
+        });
+    }
+    return Ok(file_checksums);
+}
@@ -0,0 +1,686 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use moth_filter::*;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    addin_tsv_hashmaps::VernacularHashKey,
+    location_codes,
+    tsv_parsing::{self, TSVMaps},
+    tsv_types::*,
+};
+
+pub const MOTH_ORDER: &str = "Lepidoptera";
+pub const BUTTERFLY_SUPERFAMILY: &str = "Papilionoidea";
+pub const TAXON_RANK: &str = "species";
+pub const COMMON_NAME_LANGUAGE: &str = "eng";
+
+// rows per chunk handed to the thread pool, enough to keep every thread busy without holding the whole file in memory
+const TAXON_CHUNK_SIZE: usize = 65_536;
+
+pub struct MothExtraction {
+    pub moth_entries: MothDataJson,
+    pub moth_synonyms: MothSynonyms,
+    pub moth_synonyms_count: usize,
+    pub butterfly_blacklist: ButterflyBlacklist,
+    pub butterfly_collisions: ButterflyBlacklist,
+    pub bad_entry_count: usize,
+    pub phase_timings: Vec<(&'static str, Duration)>,
+}
+
+#[derive(Clone, Copy)]
+pub enum SpeciesSortOrder {
+    Classification,
+    TaxonId,
+    // order of `Taxon.tsv`, stable between runs but not between releases
+    Source,
+}
+
+impl std::fmt::Display for SpeciesSortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            SpeciesSortOrder::Classification => write!(f, "classification"),
+            SpeciesSortOrder::TaxonId => write!(f, "id"),
+            SpeciesSortOrder::Source => write!(f, "source"),
+        };
+    }
+}
+
+impl std::str::FromStr for SpeciesSortOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<SpeciesSortOrder, String> {
+        return match value {
+            "classification" => Ok(SpeciesSortOrder::Classification),
+            "id" => Ok(SpeciesSortOrder::TaxonId),
+            "source" => Ok(SpeciesSortOrder::Source),
+            _ => Err(format!(
+                "Unknown sort order `{value}`, expected classification, id or source"
+            )),
+        };
+    }
+}
+
+// what a single `Taxon.tsv` row contributes, merged in file order so the output doesn't depend on thread scheduling
+enum TaxonRowOutcome {
+    Bad,
+    Skipped,
+    Synonym {
+        accepted_taxon_id: String,
+        synonym: SynonymSpecies,
+    },
+    Butterfly(Box<TaxonTSVRaw>),
+    Moth(Box<SpeciesData>),
+    // counted as a moth for its synonyms but missing a genus or epithet
+    BadMoth(String),
+}
+
+/// Builds all outputs from the Darwin Core Archive in `data_dir`.
+/// With `low_memory` the moth ids are collected from `Taxon.tsv` first so only their side table rows are loaded.
+pub fn extract_moth_data(data_dir: &str, low_memory: bool) -> MothExtraction {
+    let mut phase_timings: Vec<(&'static str, Duration)> = Vec::new();
+
+    let moth_taxon_ids = if low_memory {
+        let phase_start_time = Instant::now();
+        let moth_taxon_ids = collect_moth_taxon_ids(data_dir);
+        phase_timings.push(("Collected moth taxon ids", phase_start_time.elapsed()));
+        Some(moth_taxon_ids)
+    } else {
+        None
+    };
+
+    let phase_start_time = Instant::now();
+    let tsv_maps = tsv_parsing::parse_tsvs(data_dir, moth_taxon_ids.as_ref());
+    drop(moth_taxon_ids);
+    phase_timings.push(("Parsed side tables", phase_start_time.elapsed()));
+
+    let phase_start_time = Instant::now();
+    let mut taxon_tsv_reader = tsv_parsing::tsv_reader(&format!("{data_dir}/Taxon.tsv"));
+    let taxon_headers = taxon_tsv_reader.headers().unwrap().clone();
+    let mut taxon_tsv = taxon_tsv_reader.into_records();
+
+    let mut bad_entry_count = 0;
+    let mut moth_entries: Vec<SpeciesData> = Vec::new();
+    let mut synonyms: HashMap<String, Vec<SynonymSpecies>> = HashMap::new();
+    let mut moth_ids: HashSet<String> = HashSet::new();
+    let mut butterfly_data: ButterflyBlacklist = Default::default();
+
+    loop {
+        let taxon_chunk: Vec<csv::Result<csv::StringRecord>> =
+            taxon_tsv.by_ref().take(TAXON_CHUNK_SIZE).collect();
+        if taxon_chunk.is_empty() {
+            break;
+        }
+
+        #[cfg(feature = "rayon")]
+        let taxon_chunk_iter = taxon_chunk.into_par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let taxon_chunk_iter = taxon_chunk.into_iter();
+        let outcomes: Vec<TaxonRowOutcome> = taxon_chunk_iter
+            .map(|x| classify_taxon_row(x, &taxon_headers, &tsv_maps))
+            .collect();
+
+        for outcome in outcomes {
+            match outcome {
+                TaxonRowOutcome::Bad => bad_entry_count += 1,
+                TaxonRowOutcome::Skipped => (),
+                TaxonRowOutcome::Synonym {
+                    accepted_taxon_id,
+                    synonym,
+                } => synonyms.entry(accepted_taxon_id).or_default().push(synonym),
+                TaxonRowOutcome::Butterfly(taxon_tsv_data_raw) => {
+                    add_to_butterfly_blacklist(&mut butterfly_data, *taxon_tsv_data_raw)
+                }
+                TaxonRowOutcome::Moth(species_data) => {
+                    moth_ids.insert(species_data.catalogue_of_life_taxon_id.clone());
+                    moth_entries.push(*species_data);
+                }
+                TaxonRowOutcome::BadMoth(taxon_id) => {
+                    moth_ids.insert(taxon_id);
+                    bad_entry_count += 1;
+                }
+            }
+        }
+    }
+    phase_timings.push(("Parsed taxa", phase_start_time.elapsed()));
+
+    let phase_start_time = Instant::now();
+    synonyms.retain(|key, _value| moth_ids.contains(key));
+    let moth_synonyms_count: usize = synonyms.iter().map(|x| x.1.len()).sum();
+
+    let mut butterfly_collision_data: ButterflyBlacklist = Default::default();
+
+    for moth_entry in moth_entries.iter_mut() {
+        // eliminate any false positives in butterfly blacklist
+        // only genera and epithets appear to collide but check over all of them anyways
+        if let Some(family) = &moth_entry.classification.family
+            && butterfly_data.families.remove(&family.to_lowercase())
+        {
+            butterfly_collision_data.families.insert(family.clone());
+        }
+        if let Some(subfamily) = &moth_entry.classification.subfamily
+            && butterfly_data.subfamilies.remove(&subfamily.to_lowercase())
+        {
+            butterfly_collision_data
+                .subfamilies
+                .insert(subfamily.clone());
+        }
+        if let Some(tribe) = &moth_entry.classification.tribe
+            && butterfly_data.tribes.remove(&tribe.to_lowercase())
+        {
+            butterfly_collision_data.tribes.insert(tribe.clone());
+        }
+        if let Some(subtribe) = &moth_entry.classification.subtribe
+            && butterfly_data.subtribes.remove(&subtribe.to_lowercase())
+        {
+            butterfly_collision_data.subtribes.insert(subtribe.clone());
+        }
+        if butterfly_data
+            .genera
+            .remove(&moth_entry.classification.genus.to_lowercase())
+        {
+            butterfly_collision_data
+                .genera
+                .insert(moth_entry.classification.genus.clone());
+        }
+        if butterfly_data
+            .epithets
+            .remove(&moth_entry.classification.epithet.to_lowercase())
+        {
+            butterfly_collision_data
+                .epithets
+                .insert(moth_entry.classification.epithet.clone());
+        }
+
+        // append synonyms
+        moth_entry.synonyms = synonyms
+            .get(&moth_entry.catalogue_of_life_taxon_id)
+            .cloned()
+            .map(|mut x| {
+                x.sort_by(|a, b| {
+                    (&a.genus, &a.epithet, &a.catalogue_of_life_taxon_id).cmp(&(
+                        &b.genus,
+                        &b.epithet,
+                        &b.catalogue_of_life_taxon_id,
+                    ))
+                });
+                x
+            });
+    }
+
+    let reversed_synonyms_data = get_reversed_synonym_map(&synonyms);
+    phase_timings.push((
+        "Attached synonyms and removed butterfly collisions",
+        phase_start_time.elapsed(),
+    ));
+
+    return MothExtraction {
+        moth_entries,
+        moth_synonyms: reversed_synonyms_data,
+        moth_synonyms_count,
+        butterfly_blacklist: butterfly_data,
+        butterfly_collisions: butterfly_collision_data,
+        bad_entry_count,
+        phase_timings,
+    };
+}
+
+fn get_reversed_synonym_map(synonyms: &HashMap<String, Vec<SynonymSpecies>>) -> MothSynonyms {
+    let mut new_synonyms: MothSynonyms = Default::default();
+    // a name can be a synonym of several accepted taxa, the lowest accepted id wins so reruns give the same map
+    let mut taxon_ids: Vec<&String> = synonyms.keys().collect();
+    taxon_ids.sort();
+    for taxon_id in taxon_ids {
+        for synonym_data in &synonyms[taxon_id] {
+            new_synonyms
+                .entry(format!("{} {}", synonym_data.genus, synonym_data.epithet))
+                .or_insert_with(|| taxon_id.to_string());
+        }
+    }
+    return new_synonyms;
+}
+
+pub fn sort_moth_entries(moth_entries: &mut [SpeciesData], sort_order: SpeciesSortOrder) {
+    match sort_order {
+        SpeciesSortOrder::Classification => moth_entries.sort_by(|a, b| {
+            a.classification.cmp(&b.classification).then_with(|| {
+                a.catalogue_of_life_taxon_id
+                    .cmp(&b.catalogue_of_life_taxon_id)
+            })
+        }),
+        SpeciesSortOrder::TaxonId => moth_entries.sort_by(|a, b| {
+            a.catalogue_of_life_taxon_id
+                .cmp(&b.catalogue_of_life_taxon_id)
+        }),
+        SpeciesSortOrder::Source => (),
+    }
+}
+
+// same checks as `classify_taxon_row` without deserializing the whole row, butterflies and synonyms don't need side table data
+fn collect_moth_taxon_ids(data_dir: &str) -> HashSet<String> {
+    let mut taxon_tsv_reader = tsv_parsing::tsv_reader(&format!("{data_dir}/Taxon.tsv"));
+    let mut moth_taxon_ids: HashSet<String> = HashSet::new();
+    for tsv_reader_result in taxon_tsv_reader.deserialize::<TaxonFilterTSVRaw>() {
+        let Ok(taxon_tsv_data_raw) = tsv_reader_result else {
+            continue;
+        };
+        if taxon_tsv_data_raw.dwc_taxon_rank != TAXON_RANK {
+            continue;
+        }
+        if matches!(
+            taxon_tsv_data_raw.dwc_taxonomic_status,
+            TaxonomicStatusRaw::Synonym
+                | TaxonomicStatusRaw::AmbiguousSynonym
+                | TaxonomicStatusRaw::Misapplied
+        ) {
+            continue;
+        }
+        if taxon_tsv_data_raw.dwc_order.as_deref() != Some(MOTH_ORDER)
+            || taxon_tsv_data_raw.dwc_superfamily.as_deref() == Some(BUTTERFLY_SUPERFAMILY)
+        {
+            continue;
+        }
+        moth_taxon_ids.insert(taxon_tsv_data_raw.dwc_taxon_id);
+    }
+    return moth_taxon_ids;
+}
+
+fn classify_taxon_row(
+    tsv_reader_result: csv::Result<csv::StringRecord>,
+    taxon_headers: &csv::StringRecord,
+    tsv_maps: &TSVMaps,
+) -> TaxonRowOutcome {
+    let Ok(taxon_tsv_data_raw) =
+        tsv_reader_result.and_then(|x| x.deserialize::<TaxonTSVRaw>(Some(taxon_headers)))
+    else {
+        return TaxonRowOutcome::Bad;
+    };
+
+    // filter out not species before checking for synonyms
+    if taxon_tsv_data_raw.dwc_taxon_rank != TAXON_RANK {
+        return TaxonRowOutcome::Skipped;
+    }
+
+    // synonyms have nearly no data and will never be detected as a moth, run before moth check and filter out non moths later
+    match taxon_tsv_data_raw.dwc_taxonomic_status {
+        TaxonomicStatusRaw::Synonym | TaxonomicStatusRaw::AmbiguousSynonym => {
+            if let Some(genus) = taxon_tsv_data_raw.dwc_generic_name
+                && let Some(epithet) = taxon_tsv_data_raw.dwc_specific_epithet
+            {
+                return TaxonRowOutcome::Synonym {
+                    accepted_taxon_id: taxon_tsv_data_raw.dwc_accepted_name_usage_id,
+                    synonym: SynonymSpecies {
+                        catalogue_of_life_taxon_id: taxon_tsv_data_raw.dwc_taxon_id,
+                        genus,
+                        epithet,
+                    },
+                };
+            }
+            return TaxonRowOutcome::Skipped;
+        }
+        TaxonomicStatusRaw::Misapplied => {
+            return TaxonRowOutcome::Skipped;
+        }
+        _ => (),
+    };
+
+    // skip if not a moth
+    let Some(order) = &taxon_tsv_data_raw.dwc_order else {
+        return TaxonRowOutcome::Skipped;
+    };
+    if order != MOTH_ORDER {
+        return TaxonRowOutcome::Skipped;
+    }
+    if let Some(superfamily) = &taxon_tsv_data_raw.dwc_superfamily
+        && superfamily == BUTTERFLY_SUPERFAMILY
+    {
+        return TaxonRowOutcome::Butterfly(Box::new(taxon_tsv_data_raw));
+    }
+
+    let common_name = tsv_maps.vernacular_name.get(&VernacularHashKey {
+        language_code: COMMON_NAME_LANGUAGE.to_string(),
+        taxon_id: taxon_tsv_data_raw.dwc_taxon_id.clone(),
+    });
+    let species_profile = tsv_maps
+        .species_profile
+        .get(&taxon_tsv_data_raw.dwc_taxon_id)
+        .map(|x| SpeciesProfile {
+            extinct: x.gbif_is_extinct,
+            freshwater: x.gbif_is_freshwater,
+            marine: x.gbif_is_marine,
+            terrestrial: x.gbif_is_terrestrial,
+            habitat: Habitat::from_flags(
+                x.gbif_is_terrestrial,
+                x.gbif_is_freshwater,
+                x.gbif_is_marine,
+            ),
+        });
+
+    let distribution = tsv_maps
+        .distribution
+        .get(&taxon_tsv_data_raw.dwc_taxon_id)
+        .and_then(|x| merge_distribution_rows(x));
+
+    // some malformed entries dont have a `genus` but have a `generic name` which is synonymous
+    let (genus_fixed, generic_name) = match taxon_tsv_data_raw.dwc_genus {
+        Some(some) => {
+            let generic_name = taxon_tsv_data_raw.dwc_generic_name.filter(|x| *x != some);
+            (some, generic_name)
+        }
+        None => match taxon_tsv_data_raw.dwc_generic_name {
+            Some(some) => (some, None),
+            None => {
+                return TaxonRowOutcome::BadMoth(taxon_tsv_data_raw.dwc_taxon_id);
+            }
+        },
+    };
+
+    let Some(epithet_checked) = taxon_tsv_data_raw.dwc_specific_epithet else {
+        return TaxonRowOutcome::BadMoth(taxon_tsv_data_raw.dwc_taxon_id);
+    };
+
+    return TaxonRowOutcome::Moth(Box::new(SpeciesData {
+        catalogue_of_life_taxon_id: taxon_tsv_data_raw.dwc_taxon_id,
+        classification: ScientificClassification {
+            superfamily: taxon_tsv_data_raw.dwc_superfamily,
+            family: taxon_tsv_data_raw.dwc_family,
+            subfamily: taxon_tsv_data_raw.dwc_subfamily,
+            tribe: taxon_tsv_data_raw.dwc_tribe,
+            subtribe: taxon_tsv_data_raw.dwc_subtribe,
+            genus: genus_fixed,
+            epithet: epithet_checked,
+            generic_name,
+        },
+        common_names: common_name.cloned(),
+        species_profile,
+        distribution,
+        synonyms: None,
+        published_in: taxon_tsv_data_raw.dwc_name_published_in,
+        authorship: Some(taxon_tsv_data_raw.dwc_scientific_name_authorship)
+            .filter(|x| !x.is_empty()),
+    }));
+}
+
+fn add_to_butterfly_blacklist(
+    butterfly_data: &mut ButterflyBlacklist,
+    taxon_tsv_data_raw: TaxonTSVRaw,
+) {
+    if let Some(family) = taxon_tsv_data_raw.dwc_family {
+        butterfly_data.families.insert(family.to_lowercase());
+    }
+    if let Some(subfamily) = taxon_tsv_data_raw.dwc_subfamily {
+        butterfly_data.subfamilies.insert(subfamily.to_lowercase());
+    }
+    if let Some(tribe) = taxon_tsv_data_raw.dwc_tribe {
+        butterfly_data.tribes.insert(tribe.to_lowercase());
+    }
+    if let Some(subtribe) = taxon_tsv_data_raw.dwc_subtribe {
+        butterfly_data.subtribes.insert(subtribe.to_lowercase());
+    }
+    if let Some(genus) = taxon_tsv_data_raw.dwc_genus {
+        butterfly_data.genera.insert(genus.to_lowercase());
+    }
+    if let Some(genus) = taxon_tsv_data_raw.dwc_generic_name {
+        butterfly_data.genera.insert(genus.to_lowercase());
+    }
+    if let Some(epithet) = taxon_tsv_data_raw.dwc_specific_epithet {
+        butterfly_data.epithets.insert(epithet.to_lowercase());
+    }
+}
+
+// merges the rows of a taxon, `None` when they have nothing worth keeping
+fn merge_distribution_rows(distribution_rows: &[DistributionTSVRaw]) -> Option<Distribution> {
+    let mut localities: Vec<String> = Vec::new();
+    let mut threat_status: Option<ThreatStatus> = None;
+    let mut source: Option<String> = None;
+    let mut country_codes: BTreeSet<String> = BTreeSet::new();
+    let mut location_ids: BTreeSet<String> = BTreeSet::new();
+    for distribution_row in distribution_rows {
+        if let Some(locality) = &distribution_row.dwc_locality
+            && !localities.contains(locality)
+        {
+            localities.push(locality.clone());
+        }
+
+        let row_threat_status = distribution_row
+            .iucn_threat_status
+            .as_ref()
+            .and_then(map_threat_status);
+        // the first row with a status is the assessment, its source is the one kept
+        if threat_status.is_none() && row_threat_status.is_some() {
+            threat_status = row_threat_status;
+            source = distribution_row.dcterms_source.clone();
+        } else if source.is_none() && threat_status.is_none() {
+            source = distribution_row.dcterms_source.clone();
+        }
+
+        if let Some(country_code) =
+            location_codes::normalize_country_code(&distribution_row.dwc_country_code)
+        {
+            country_codes.insert(country_code);
+        }
+        if let Some(location_id) =
+            location_codes::normalize_location_id(&distribution_row.dwc_location_id)
+        {
+            if let Some(country_code) = location_codes::location_id_country_code(&location_id) {
+                country_codes.insert(country_code);
+            }
+            location_ids.insert(location_id);
+        }
+    }
+
+    if localities.is_empty()
+        && threat_status.is_none()
+        && country_codes.is_empty()
+        && location_ids.is_empty()
+    {
+        return None;
+    }
+    return Some(Distribution {
+        locality: localities.first().cloned(),
+        localities: (!localities.is_empty()).then_some(localities),
+        threat_status,
+        source,
+        country_codes: (!country_codes.is_empty()).then(|| country_codes.into_iter().collect()),
+        location_ids: (!location_ids.is_empty()).then(|| location_ids.into_iter().collect()),
+    });
+}
+
+fn map_threat_status(threat_status_raw: &ThreatStatusRaw) -> Option<ThreatStatus> {
+    return match threat_status_raw {
+        ThreatStatusRaw::LeastConcern => Some(ThreatStatus::LeastConcern),
+        ThreatStatusRaw::NearThreatened => Some(ThreatStatus::NearThreatened),
+        ThreatStatusRaw::Vulnerable => Some(ThreatStatus::Vulnerable),
+        ThreatStatusRaw::Endangered => Some(ThreatStatus::Endangered),
+        ThreatStatusRaw::CriticallyEndangered => Some(ThreatStatus::CriticallyEndangered),
+        ThreatStatusRaw::ExtinctInTheWild => Some(ThreatStatus::ExtinctInTheWild),
+        ThreatStatusRaw::Extinct => Some(ThreatStatus::Extinct),
+        ThreatStatusRaw::NotEvaluated => Some(ThreatStatus::NotEvaluated),
+        ThreatStatusRaw::DataDeficient => Some(ThreatStatus::DataDeficient),
+        ThreatStatusRaw::LowerRiskConservationDependent => {
+            Some(ThreatStatus::ConservationDependent)
+        }
+        ThreatStatusRaw::LowerRiskNearThreatened => Some(ThreatStatus::NearThreatened),
+        ThreatStatusRaw::LowerRiskLeastConcern => Some(ThreatStatus::LeastConcern),
+        // unrecognised statuses are dropped, the rest of the distribution is still useful
+        ThreatStatusRaw::Unknown(_) => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn synonym(taxon_id: &str, genus: &str, epithet: &str) -> SynonymSpecies {
+        return SynonymSpecies {
+            catalogue_of_life_taxon_id: taxon_id.to_string(),
+            genus: genus.to_string(),
+            epithet: epithet.to_string(),
+        };
+    }
+
+    // every output that is written as json, in the form it is written
+    fn serialized_outputs(low_memory: bool) -> Vec<String> {
+        let mut moth_extraction = extract_moth_data(FIXTURE_DATA_DIR, low_memory);
+        sort_moth_entries(
+            &mut moth_extraction.moth_entries,
+            SpeciesSortOrder::Classification,
+        );
+        return vec![
+            serde_json::to_string_pretty(&moth_extraction.moth_entries).unwrap(),
+            serde_json::to_string_pretty(&sorted_moth_synonyms(&moth_extraction.moth_synonyms))
+                .unwrap(),
+            serde_json::to_string_pretty(&moth_extraction.butterfly_blacklist).unwrap(),
+            serde_json::to_string_pretty(&moth_extraction.butterfly_collisions).unwrap(),
+        ];
+    }
+
+    #[test]
+    fn parses_threat_status_codes_and_names() {
+        let threat_status = |value: &str| ThreatStatusRaw::from(value.to_string());
+        assert!(matches!(
+            threat_status("NT"),
+            ThreatStatusRaw::NearThreatened
+        ));
+        assert!(matches!(
+            threat_status(" near threatened"),
+            ThreatStatusRaw::NearThreatened
+        ));
+        assert!(matches!(
+            threat_status("Critically_Endangered"),
+            ThreatStatusRaw::CriticallyEndangered
+        ));
+        assert!(matches!(
+            threat_status("LR / cd"),
+            ThreatStatusRaw::LowerRiskConservationDependent
+        ));
+        assert!(matches!(
+            threat_status("Lower Risk/least concern"),
+            ThreatStatusRaw::LowerRiskLeastConcern
+        ));
+        assert!(matches!(
+            threat_status("probably fine"),
+            ThreatStatusRaw::Unknown(x) if x == "probably fine"
+        ));
+    }
+
+    #[test]
+    fn maps_old_threat_categories_to_current_ones() {
+        let threat_status =
+            |value: &str| map_threat_status(&ThreatStatusRaw::from(value.to_string()));
+        assert!(matches!(
+            threat_status("DD"),
+            Some(ThreatStatus::DataDeficient)
+        ));
+        assert!(matches!(
+            threat_status("lr/nt"),
+            Some(ThreatStatus::NearThreatened)
+        ));
+        assert!(matches!(
+            threat_status("lr/lc"),
+            Some(ThreatStatus::LeastConcern)
+        ));
+        assert!(matches!(
+            threat_status("lr/cd"),
+            Some(ThreatStatus::ConservationDependent)
+        ));
+        assert!(threat_status("probably fine").is_none());
+
+        let moth_extraction = extract_moth_data(FIXTURE_DATA_DIR, false);
+        let agrotis_ipsilon = moth_extraction
+            .moth_entries
+            .iter()
+            .find(|x| x.catalogue_of_life_taxon_id == "6XQTZ")
+            .unwrap();
+        let distribution = agrotis_ipsilon.distribution.as_ref().unwrap();
+        assert!(matches!(
+            distribution.threat_status,
+            Some(ThreatStatus::LeastConcern)
+        ));
+        assert_eq!(distribution.source.as_deref(), Some("IUCN Red List 2021"));
+    }
+
+    #[test]
+    fn merges_distribution_rows() {
+        let moth_extraction = extract_moth_data(FIXTURE_DATA_DIR, false);
+        let distribution = |taxon_id: &str| -> &Distribution {
+            return moth_extraction
+                .moth_entries
+                .iter()
+                .find(|x| x.catalogue_of_life_taxon_id == taxon_id)
+                .and_then(|x| x.distribution.as_ref())
+                .unwrap();
+        };
+        let agrotis_ipsilon = distribution("6XQTZ");
+        assert_eq!(agrotis_ipsilon.locality.as_deref(), Some("United Kingdom"));
+        assert_eq!(
+            agrotis_ipsilon.localities.as_deref(),
+            Some(["United Kingdom".to_string(), "United States".to_string()].as_slice())
+        );
+        assert_eq!(
+            agrotis_ipsilon.country_codes.as_deref(),
+            Some(["GB".to_string(), "US".to_string()].as_slice())
+        );
+        assert_eq!(
+            agrotis_ipsilon.location_ids.as_deref(),
+            Some(["iso:GB".to_string(), "iso:US".to_string()].as_slice())
+        );
+        let tuta_absoluta = distribution("3FQ8H");
+        assert_eq!(tuta_absoluta.locality.as_deref(), Some("Spain"));
+        assert!(tuta_absoluta.threat_status.is_none());
+    }
+
+    #[test]
+    fn picks_lowest_accepted_id_for_ambiguous_synonyms() {
+        let synonyms: HashMap<String, Vec<SynonymSpecies>> = HashMap::from([
+            ("C".to_string(), vec![synonym("S3", "Phalaena", "nupta")]),
+            (
+                "A".to_string(),
+                vec![
+                    synonym("S1", "Phalaena", "nupta"),
+                    synonym("S4", "Noctua", "nupta"),
+                ],
+            ),
+            ("B".to_string(), vec![synonym("S2", "Phalaena", "nupta")]),
+        ]);
+        let moth_synonyms = get_reversed_synonym_map(&synonyms);
+        assert_eq!(moth_synonyms.len(), 2);
+        assert_eq!(moth_synonyms["Phalaena nupta"], "A");
+        assert_eq!(moth_synonyms["Noctua nupta"], "A");
+    }
+
+    #[test]
+    fn writes_identical_outputs_between_runs() {
+        let outputs = serialized_outputs(false);
+        assert_eq!(outputs, serialized_outputs(false));
+        assert_eq!(outputs, serialized_outputs(true));
+
+        let moth_extraction = extract_moth_data(FIXTURE_DATA_DIR, false);
+        assert_eq!(
+            moth_extraction
+                .moth_synonyms
+                .get("Feltia ipsilon")
+                .map(String::as_str),
+            Some("6XQTZ")
+        );
+        assert!(
+            moth_extraction
+                .butterfly_blacklist
+                .genera
+                .contains("vanessa")
+        );
+        assert!(
+            moth_extraction
+                .moth_entries
+                .iter()
+                .all(|x| x.classification.genus != "Vanessa")
+        );
+    }
+}
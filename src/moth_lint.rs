@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SpeciesData, species_name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    MissingSuperfamily,
+    MissingFamily,
+    /// `dwc:genus` and `dwc:genericName` name different genera.
+    GenusMismatch,
+    InvalidEpithet,
+    /// More than one accepted species with the same genus and epithet.
+    DuplicateName,
+    /// No `dwc:parentNameUsageID`.
+    NoParent,
+    /// The same common name on species of different genera.
+    SharedCommonName,
+}
+
+impl std::fmt::Display for LintKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            LintKind::MissingSuperfamily => write!(f, "Missing superfamily"),
+            LintKind::MissingFamily => write!(f, "Missing family"),
+            LintKind::GenusMismatch => write!(f, "Genus and generic name disagree"),
+            LintKind::InvalidEpithet => write!(f, "Invalid epithet"),
+            LintKind::DuplicateName => write!(f, "Duplicate accepted name"),
+            LintKind::NoParent => write!(f, "No parent"),
+            LintKind::SharedCommonName => write!(f, "Common name shared across genera"),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    pub catalogue_of_life_taxon_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl LintIssue {
+    pub fn new(kind: LintKind, species: &SpeciesData, detail: Option<String>) -> LintIssue {
+        return LintIssue {
+            kind,
+            catalogue_of_life_taxon_id: species.catalogue_of_life_taxon_id.clone(),
+            name: species_name(species),
+            detail,
+        };
+    }
+}
+
+/// Checks that only need the generated moth data, issues are ordered like `moth_data`.
+pub fn lint_moth_data(moth_data: &[SpeciesData]) -> Vec<LintIssue> {
+    let mut lint_issues: Vec<LintIssue> = Vec::new();
+
+    let mut species_by_name: HashMap<String, Vec<&SpeciesData>> = HashMap::new();
+    // lowercase common name -> species using it
+    let mut species_by_common_name: HashMap<String, Vec<&SpeciesData>> = HashMap::new();
+    for species in moth_data {
+        species_by_name
+            .entry(species_name(species))
+            .or_default()
+            .push(species);
+        for common_name in species.common_names.iter().flatten() {
+            let common_name_species = species_by_common_name
+                .entry(common_name.to_lowercase())
+                .or_default();
+            // names can repeat on one species in different spellings
+            if !common_name_species
+                .iter()
+                .any(|x| x.catalogue_of_life_taxon_id == species.catalogue_of_life_taxon_id)
+            {
+                common_name_species.push(species);
+            }
+        }
+    }
+
+    for species in moth_data {
+        let classification = &species.classification;
+        if classification.superfamily.is_none() {
+            lint_issues.push(LintIssue::new(LintKind::MissingSuperfamily, species, None));
+        }
+        if classification.family.is_none() {
+            lint_issues.push(LintIssue::new(LintKind::MissingFamily, species, None));
+        }
+        if let Some(generic_name) = &classification.generic_name {
+            lint_issues.push(LintIssue::new(
+                LintKind::GenusMismatch,
+                species,
+                Some(format!(
+                    "genus `{}`, generic name `{generic_name}`",
+                    classification.genus
+                )),
+            ));
+        }
+        if !is_valid_epithet(&classification.epithet) {
+            lint_issues.push(LintIssue::new(
+                LintKind::InvalidEpithet,
+                species,
+                Some(format!("`{}`", classification.epithet)),
+            ));
+        }
+
+        if let Some(same_name_species) = species_by_name.get(&species_name(species))
+            && same_name_species.len() > 1
+        {
+            lint_issues.push(LintIssue::new(
+                LintKind::DuplicateName,
+                species,
+                Some(format!(
+                    "also {}",
+                    other_taxon_ids(same_name_species, species).join(", ")
+                )),
+            ));
+        }
+
+        for common_name in species.common_names.iter().flatten() {
+            let Some(common_name_species) = species_by_common_name.get(&common_name.to_lowercase())
+            else {
+                continue;
+            };
+            let other_genus_species: Vec<&SpeciesData> = common_name_species
+                .iter()
+                .filter(|x| x.classification.genus != classification.genus)
+                .copied()
+                .collect();
+            if other_genus_species.is_empty() {
+                continue;
+            }
+            lint_issues.push(LintIssue::new(
+                LintKind::SharedCommonName,
+                species,
+                Some(format!(
+                    "`{common_name}` also on {}",
+                    other_genus_species
+                        .iter()
+                        .map(|x| format!("{} ({})", species_name(x), x.catalogue_of_life_taxon_id))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )),
+            ));
+        }
+    }
+
+    return lint_issues;
+}
+
+/// Issue counts per kind, for summaries.
+pub fn count_lint_issues(lint_issues: &[LintIssue]) -> BTreeMap<LintKind, usize> {
+    let mut lint_issue_counts: BTreeMap<LintKind, usize> = BTreeMap::new();
+    for lint_issue in lint_issues {
+        *lint_issue_counts.entry(lint_issue.kind).or_default() += 1;
+    }
+    return lint_issue_counts;
+}
+
+// lowercase latin letters, with hyphens as in `c-album`
+fn is_valid_epithet(epithet: &str) -> bool {
+    return !epithet.is_empty()
+        && !epithet.starts_with('-')
+        && !epithet.ends_with('-')
+        && epithet.chars().all(|x| x.is_ascii_lowercase() || x == '-');
+}
+
+fn other_taxon_ids(species_list: &[&SpeciesData], species: &SpeciesData) -> Vec<String> {
+    return species_list
+        .iter()
+        .filter(|x| x.catalogue_of_life_taxon_id != species.catalogue_of_life_taxon_id)
+        .map(|x| x.catalogue_of_life_taxon_id.clone())
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::species;
+
+    // a fully classified species that passes every check
+    fn clean_species(taxon_id: &str, genus: &str, epithet: &str) -> SpeciesData {
+        let mut species = species(taxon_id, genus, epithet);
+        species.classification.superfamily = Some("Noctuoidea".to_string());
+        species.classification.family = Some("Noctuidae".to_string());
+        return species;
+    }
+
+    fn lint_kinds(moth_data: &[SpeciesData]) -> Vec<(LintKind, String)> {
+        return lint_moth_data(moth_data)
+            .into_iter()
+            .map(|x| (x.kind, x.catalogue_of_life_taxon_id))
+            .collect();
+    }
+
+    #[test]
+    fn passes_clean_moth_data() {
+        let mut agrotis_ipsilon = clean_species("1", "Agrotis", "ipsilon");
+        agrotis_ipsilon.common_names = Some(vec![
+            "Dark Sword-grass".to_string(),
+            "dark sword-grass".to_string(),
+        ]);
+        let mut agrotis_segetum = clean_species("2", "Agrotis", "segetum");
+        // shared within one genus is fine
+        agrotis_segetum.common_names = Some(vec!["Dark Sword-grass".to_string()]);
+        let moth_data = vec![
+            agrotis_ipsilon,
+            agrotis_segetum,
+            clean_species("3", "Xestia", "c-nigrum"),
+        ];
+        assert!(lint_moth_data(&moth_data).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_ranks() {
+        let mut no_superfamily = clean_species("1", "Agrotis", "ipsilon");
+        no_superfamily.classification.superfamily = None;
+        let moth_data = vec![no_superfamily, species("2", "Agrotis", "segetum")];
+        assert_eq!(
+            lint_kinds(&moth_data),
+            [
+                (LintKind::MissingSuperfamily, "1".to_string()),
+                (LintKind::MissingSuperfamily, "2".to_string()),
+                (LintKind::MissingFamily, "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_genus_mismatch() {
+        let mut agrotis_ipsilon = clean_species("1", "Agrotis", "ipsilon");
+        agrotis_ipsilon.classification.generic_name = Some("Feltia".to_string());
+        let lint_issues = lint_moth_data(&[agrotis_ipsilon]);
+        assert_eq!(lint_issues.len(), 1);
+        assert_eq!(lint_issues[0].kind, LintKind::GenusMismatch);
+        assert_eq!(
+            lint_issues[0].detail.as_deref(),
+            Some("genus `Agrotis`, generic name `Feltia`")
+        );
+    }
+
+    #[test]
+    fn reports_invalid_epithets() {
+        let moth_data = vec![
+            clean_species("1", "Agrotis", "Ipsilon"),
+            clean_species("2", "Xestia", "c-"),
+            clean_species("3", "Xestia", "nigrum2"),
+            clean_species("4", "Xestia", ""),
+        ];
+        assert_eq!(
+            lint_kinds(&moth_data)
+                .into_iter()
+                .filter(|(kind, _)| *kind == LintKind::InvalidEpithet)
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_names_on_every_homonym() {
+        let moth_data = vec![
+            clean_species("1", "Agrotis", "alba"),
+            clean_species("2", "Agrotis", "segetum"),
+            clean_species("3", "Agrotis", "alba"),
+        ];
+        let lint_issues = lint_moth_data(&moth_data);
+        assert_eq!(lint_issues.len(), 2);
+        assert!(
+            lint_issues
+                .iter()
+                .all(|x| x.kind == LintKind::DuplicateName)
+        );
+        assert_eq!(lint_issues[0].catalogue_of_life_taxon_id, "1");
+        assert_eq!(lint_issues[0].detail.as_deref(), Some("also 3"));
+        assert_eq!(lint_issues[1].detail.as_deref(), Some("also 1"));
+    }
+
+    #[test]
+    fn reports_common_names_shared_across_genera() {
+        let mut agrotis_ipsilon = clean_species("1", "Agrotis", "ipsilon");
+        agrotis_ipsilon.common_names = Some(vec!["Black Cutworm".to_string()]);
+        let mut euxoa_ochrogaster = clean_species("2", "Euxoa", "ochrogaster");
+        euxoa_ochrogaster.common_names = Some(vec!["black cutworm".to_string()]);
+        let lint_issues = lint_moth_data(&[agrotis_ipsilon, euxoa_ochrogaster]);
+        assert_eq!(lint_issues.len(), 2);
+        assert!(
+            lint_issues
+                .iter()
+                .all(|x| x.kind == LintKind::SharedCommonName)
+        );
+        assert_eq!(
+            lint_issues[0].detail.as_deref(),
+            Some("`Black Cutworm` also on Euxoa ochrogaster (2)")
+        );
+    }
+
+    #[test]
+    fn counts_issues_per_kind() {
+        let moth_data = vec![
+            species("1", "Agrotis", "alba"),
+            species("2", "Agrotis", "alba"),
+        ];
+        let lint_issue_counts = count_lint_issues(&lint_moth_data(&moth_data));
+        assert_eq!(
+            lint_issue_counts.into_iter().collect::<Vec<_>>(),
+            [
+                (LintKind::MissingSuperfamily, 2),
+                (LintKind::MissingFamily, 2),
+                (LintKind::DuplicateName, 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ButterflyBlacklist, FuzzyMatcher, MothDataJson, MothSynonyms, SpeciesData,
+    parse_scientific_name,
+};
+
+// a `cf.`, `aff.` or `nr.` identification only says the specimen is close to the named species
+const QUALIFIED_NAME_CONFIDENCE_FACTOR: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType {
+    Accepted,
+    Synonym,
+    Fuzzy,
+    NoMatch,
+}
+
+impl fmt::Display for MatchType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(match self {
+            MatchType::Accepted => "accepted",
+            MatchType::Synonym => "synonym",
+            MatchType::Fuzzy => "fuzzy",
+            MatchType::NoMatch => "no_match",
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LepidopteraGroup {
+    Moth,
+    Butterfly,
+    Neither,
+}
+
+impl fmt::Display for LepidopteraGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(match self {
+            LepidopteraGroup::Moth => "moth",
+            LepidopteraGroup::Butterfly => "butterfly",
+            LepidopteraGroup::Neither => "neither",
+        });
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameResolution {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub catalogue_of_life_taxon_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accepted_name: Option<String>,
+    pub match_type: MatchType,
+    pub confidence: f64,
+    pub group: LepidopteraGroup,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassificationQuery {
+    pub family: Option<String>,
+    pub subfamily: Option<String>,
+    pub tribe: Option<String>,
+    pub subtribe: Option<String>,
+    pub genus: Option<String>,
+    pub epithet: Option<String>,
+}
+
+// lowercased names of every rank that has at least one moth species
+#[derive(Default)]
+struct MothRankNames {
+    families: HashSet<String>,
+    subfamilies: HashSet<String>,
+    tribes: HashSet<String>,
+    subtribes: HashSet<String>,
+    genera: HashSet<String>,
+}
+
+/// All three outputs loaded together with indexes for looking up species by id or name.
+pub struct MothLookup {
+    pub moth_data: MothDataJson,
+    pub moth_synonyms: MothSynonyms,
+    pub butterfly_blacklist: ButterflyBlacklist,
+    id_index: HashMap<String, usize>,
+    accepted_name_index: HashMap<String, usize>,
+    moth_rank_names: MothRankNames,
+    fuzzy_matcher: FuzzyMatcher,
+}
+
+impl MothLookup {
+    pub fn new(
+        moth_data: MothDataJson,
+        moth_synonyms: MothSynonyms,
+        butterfly_blacklist: ButterflyBlacklist,
+    ) -> MothLookup {
+        let mut id_index = HashMap::new();
+        let mut accepted_name_index = HashMap::new();
+        let mut moth_rank_names: MothRankNames = Default::default();
+        for (index, species) in moth_data.iter().enumerate() {
+            id_index.insert(species.catalogue_of_life_taxon_id.clone(), index);
+            // homonyms among accepted names resolve to the first entry
+            accepted_name_index
+                .entry(species_name(species).to_lowercase())
+                .or_insert(index);
+            let classification = &species.classification;
+            // the name as published is still in use when the genus disagrees
+            if let Some(generic_name) = &classification.generic_name {
+                accepted_name_index
+                    .entry(format!("{} {}", generic_name, classification.epithet).to_lowercase())
+                    .or_insert(index);
+            }
+            for (rank_names, name) in [
+                (&mut moth_rank_names.families, &classification.family),
+                (&mut moth_rank_names.subfamilies, &classification.subfamily),
+                (&mut moth_rank_names.tribes, &classification.tribe),
+                (&mut moth_rank_names.subtribes, &classification.subtribe),
+            ] {
+                if let Some(name) = name {
+                    rank_names.insert(name.to_lowercase());
+                }
+            }
+            moth_rank_names
+                .genera
+                .insert(classification.genus.to_lowercase());
+        }
+        let fuzzy_matcher = FuzzyMatcher::new(&moth_data);
+        return MothLookup {
+            moth_data,
+            moth_synonyms,
+            butterfly_blacklist,
+            id_index,
+            accepted_name_index,
+            moth_rank_names,
+            fuzzy_matcher,
+        };
+    }
+
+    pub fn species_by_id(&self, taxon_id: &str) -> Option<&SpeciesData> {
+        return self.id_index.get(taxon_id).map(|x| &self.moth_data[*x]);
+    }
+
+    /// Accepted species for an exact `Genus epithet`, ignoring case.
+    pub fn species_by_name(&self, name: &str) -> Option<&SpeciesData> {
+        return self
+            .accepted_name_index
+            .get(&name.to_lowercase())
+            .map(|x| &self.moth_data[*x]);
+    }
+
+    pub fn fuzzy_matcher(&self) -> &FuzzyMatcher {
+        return &self.fuzzy_matcher;
+    }
+
+    /// Resolves a name through accepted names, then synonyms, then optionally fuzzy matching.
+    /// Qualified names like `Catocala cf. nupta` resolve to the named species with lowered confidence.
+    pub fn resolve_name(&self, name: &str, fuzzy_min_score: Option<f64>) -> NameResolution {
+        let Some(parsed_name) = parse_scientific_name(name) else {
+            return NameResolution {
+                catalogue_of_life_taxon_id: None,
+                accepted_name: None,
+                match_type: MatchType::NoMatch,
+                confidence: 0.0,
+                group: LepidopteraGroup::Neither,
+            };
+        };
+        let genus_group = self.genus_group(&parsed_name.genus);
+        let confidence_factor = if parsed_name.qualifiers.is_empty() {
+            1.0
+        } else {
+            QUALIFIED_NAME_CONFIDENCE_FACTOR
+        };
+
+        if let Some(species_key) = parsed_name.species_key() {
+            if let Some(species) = self.species_by_name(&species_key) {
+                return resolved(species, MatchType::Accepted, confidence_factor);
+            }
+            if let Some(species) = self
+                .moth_synonyms
+                .get(&species_key)
+                .and_then(|x| self.species_by_id(x))
+            {
+                return resolved(species, MatchType::Synonym, confidence_factor);
+            }
+            if let Some(fuzzy_min_score) = fuzzy_min_score
+                && let Some(fuzzy_match) = self
+                    .fuzzy_matcher
+                    .find_matches(name, fuzzy_min_score, 1)
+                    .first()
+                && let Some(species) = self.species_by_id(&fuzzy_match.catalogue_of_life_taxon_id)
+            {
+                return resolved(
+                    species,
+                    MatchType::Fuzzy,
+                    fuzzy_match.score * confidence_factor,
+                );
+            }
+        }
+
+        return NameResolution {
+            catalogue_of_life_taxon_id: None,
+            accepted_name: None,
+            match_type: MatchType::NoMatch,
+            confidence: 0.0,
+            group: genus_group,
+        };
+    }
+
+    /// Moth or butterfly by genus alone, for names that do not resolve to a species.
+    pub fn genus_group(&self, genus: &str) -> LepidopteraGroup {
+        return self.classify(&ClassificationQuery {
+            genus: Some(genus.to_string()),
+            ..Default::default()
+        });
+    }
+
+    /// Butterfly if any given rank is in the butterfly blacklist, moth if any given rank is known from moth species.
+    pub fn classify(&self, query: &ClassificationQuery) -> LepidopteraGroup {
+        let blacklist = &self.butterfly_blacklist;
+        let rank_names = &self.moth_rank_names;
+        let ranks = [
+            (
+                &query.family,
+                &blacklist.families,
+                Some(&rank_names.families),
+            ),
+            (
+                &query.subfamily,
+                &blacklist.subfamilies,
+                Some(&rank_names.subfamilies),
+            ),
+            (&query.tribe, &blacklist.tribes, Some(&rank_names.tribes)),
+            (
+                &query.subtribe,
+                &blacklist.subtribes,
+                Some(&rank_names.subtribes),
+            ),
+            (&query.genus, &blacklist.genera, Some(&rank_names.genera)),
+            // epithets alone say nothing about a species being a moth
+            (&query.epithet, &blacklist.epithets, None),
+        ];
+        let mut group = LepidopteraGroup::Neither;
+        for (name, butterfly_names, moth_names) in ranks {
+            let Some(name) = name else {
+                continue;
+            };
+            let name_lowercase = name.to_lowercase();
+            if butterfly_names.contains(&name_lowercase) {
+                return LepidopteraGroup::Butterfly;
+            }
+            if moth_names.is_some_and(|x| x.contains(&name_lowercase)) {
+                group = LepidopteraGroup::Moth;
+            }
+        }
+        return group;
+    }
+
+    /// Species whose name, higher classification or common names contain the query, ignoring case.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&SpeciesData> {
+        let query_lowercase = query.to_lowercase();
+        return self
+            .moth_data
+            .iter()
+            .filter(|species| {
+                let classification = &species.classification;
+                species_name(species)
+                    .to_lowercase()
+                    .contains(&query_lowercase)
+                    || [
+                        &classification.superfamily,
+                        &classification.family,
+                        &classification.subfamily,
+                        &classification.tribe,
+                        &classification.subtribe,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .any(|x| x.to_lowercase().contains(&query_lowercase))
+                    || species
+                        .common_names
+                        .iter()
+                        .flatten()
+                        .any(|x| x.to_lowercase().contains(&query_lowercase))
+            })
+            .take(limit)
+            .collect();
+    }
+}
+
+fn resolved(species: &SpeciesData, match_type: MatchType, confidence: f64) -> NameResolution {
+    return NameResolution {
+        catalogue_of_life_taxon_id: Some(species.catalogue_of_life_taxon_id.clone()),
+        accepted_name: Some(species_name(species)),
+        match_type,
+        confidence,
+        group: LepidopteraGroup::Moth,
+    };
+}
+
+/// `Genus epithet` of an accepted species.
+pub fn species_name(species: &SpeciesData) -> String {
+    return format!(
+        "{} {}",
+        species.classification.genus, species.classification.epithet
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{species, synonym};
+
+    fn moth_lookup() -> MothLookup {
+        let mut nupta = species("1", "Catocala", "nupta");
+        nupta.classification.family = Some("Erebidae".to_string());
+        nupta.common_names = Some(vec!["Red Underwing".to_string()]);
+        nupta.synonyms = Some(vec![synonym("3", "Phalaena", "nupta")]);
+        let moth_data = vec![nupta, species("2", "Actias", "luna")];
+        let moth_synonyms = MothSynonyms::from([("Phalaena nupta".to_string(), "1".to_string())]);
+        let mut butterfly_blacklist: ButterflyBlacklist = Default::default();
+        butterfly_blacklist.genera.insert("vanessa".to_string());
+        return MothLookup::new(moth_data, moth_synonyms, butterfly_blacklist);
+    }
+
+    #[test]
+    fn resolves_accepted_name() {
+        let resolution = moth_lookup().resolve_name("Catocala nupta (Linnaeus, 1767)", None);
+        assert_eq!(resolution.match_type, MatchType::Accepted);
+        assert_eq!(resolution.catalogue_of_life_taxon_id.as_deref(), Some("1"));
+        assert_eq!(resolution.accepted_name.as_deref(), Some("Catocala nupta"));
+        assert_eq!(resolution.confidence, 1.0);
+        assert_eq!(resolution.group, LepidopteraGroup::Moth);
+    }
+
+    #[test]
+    fn resolves_synonym() {
+        let resolution = moth_lookup().resolve_name("Phalaena nupta", None);
+        assert_eq!(resolution.match_type, MatchType::Synonym);
+        assert_eq!(resolution.catalogue_of_life_taxon_id.as_deref(), Some("1"));
+        assert_eq!(resolution.accepted_name.as_deref(), Some("Catocala nupta"));
+        assert_eq!(resolution.confidence, 1.0);
+    }
+
+    #[test]
+    fn resolves_fuzzy_only_when_enabled() {
+        let moth_lookup = moth_lookup();
+        let resolution = moth_lookup.resolve_name("Actias lunna", Some(0.7));
+        assert_eq!(resolution.match_type, MatchType::Fuzzy);
+        assert_eq!(resolution.catalogue_of_life_taxon_id.as_deref(), Some("2"));
+        assert!(resolution.confidence < 1.0 && resolution.confidence >= 0.7);
+
+        let resolution = moth_lookup.resolve_name("Actias lunna", None);
+        assert_eq!(resolution.match_type, MatchType::NoMatch);
+        assert_eq!(resolution.confidence, 0.0);
+        assert_eq!(resolution.group, LepidopteraGroup::Moth);
+    }
+
+    #[test]
+    fn resolves_no_match_with_genus_group() {
+        let moth_lookup = moth_lookup();
+        let resolution = moth_lookup.resolve_name("Vanessa cardui", Some(0.7));
+        assert_eq!(resolution.match_type, MatchType::NoMatch);
+        assert_eq!(resolution.catalogue_of_life_taxon_id, None);
+        assert_eq!(resolution.group, LepidopteraGroup::Butterfly);
+
+        let resolution = moth_lookup.resolve_name("Catocala sp.", Some(0.7));
+        assert_eq!(resolution.match_type, MatchType::NoMatch);
+        assert_eq!(resolution.group, LepidopteraGroup::Moth);
+
+        let resolution = moth_lookup.resolve_name("", Some(0.7));
+        assert_eq!(resolution.match_type, MatchType::NoMatch);
+        assert_eq!(resolution.group, LepidopteraGroup::Neither);
+    }
+
+    #[test]
+    fn lowers_confidence_of_qualified_names() {
+        let moth_lookup = moth_lookup();
+        for name in [
+            "Catocala cf. nupta",
+            "Catocala aff. nupta",
+            "Catocala nr. nupta",
+        ] {
+            let resolution = moth_lookup.resolve_name(name, None);
+            assert_eq!(resolution.match_type, MatchType::Accepted);
+            assert_eq!(resolution.catalogue_of_life_taxon_id.as_deref(), Some("1"));
+            assert_eq!(resolution.confidence, QUALIFIED_NAME_CONFIDENCE_FACTOR);
+        }
+        let resolution = moth_lookup.resolve_name("Phalaena cf. nupta", None);
+        assert_eq!(resolution.match_type, MatchType::Synonym);
+        assert_eq!(resolution.confidence, QUALIFIED_NAME_CONFIDENCE_FACTOR);
+    }
+
+    #[test]
+    fn searches_names_ranks_and_common_names() {
+        let moth_lookup = moth_lookup();
+        let taxon_ids = |query: &str| {
+            moth_lookup
+                .search(query, 10)
+                .into_iter()
+                .map(|x| x.catalogue_of_life_taxon_id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(taxon_ids("LUNA"), ["2"]);
+        assert_eq!(taxon_ids("erebid"), ["1"]);
+        assert_eq!(taxon_ids("underwing"), ["1"]);
+        assert_eq!(taxon_ids("a"), ["1", "2"]);
+        assert_eq!(moth_lookup.search("a", 1).len(), 1);
+        assert!(taxon_ids("vanessa").is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::SpeciesData;
+
+pub const DEFAULT_TOP_GENERA: usize = 20;
+// rank name used for species missing that rank
+const UNASSIGNED_RANK_NAME: &str = "(unassigned)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankCount {
+    pub name: String,
+    pub species: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeciesShare {
+    pub species: usize,
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynonymStats {
+    pub total: usize,
+    pub species_with_synonyms: SpeciesShare,
+    pub mean_per_species: f64,
+    pub max_per_species: usize,
+    /// Number of species having each synonym count, index is the synonym count.
+    pub species_per_synonym_count: Vec<usize>,
+}
+
+/// Summary of a release, for release notes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MothStats {
+    pub species: usize,
+    pub with_common_names: SpeciesShare,
+    pub with_distribution: SpeciesShare,
+    pub with_threat_status: SpeciesShare,
+    pub extinct: SpeciesShare,
+    pub marine: SpeciesShare,
+    pub freshwater: SpeciesShare,
+    pub terrestrial: SpeciesShare,
+    pub synonyms: SynonymStats,
+    pub superfamilies: Vec<RankCount>,
+    pub families: Vec<RankCount>,
+    pub subfamilies: Vec<RankCount>,
+    pub largest_genera: Vec<RankCount>,
+}
+
+impl MothStats {
+    pub fn new(moth_data: &[SpeciesData], top_genera: usize) -> MothStats {
+        let species_share = |filter: fn(&SpeciesData) -> bool| -> SpeciesShare {
+            let species = moth_data.iter().filter(|x| filter(x)).count();
+            return SpeciesShare {
+                species,
+                share: share(species, moth_data.len()),
+            };
+        };
+
+        let synonym_counts: Vec<usize> = moth_data
+            .iter()
+            .map(|x| x.synonyms.as_ref().map_or(0, |x| x.len()))
+            .collect();
+        let max_per_species = synonym_counts.iter().copied().max().unwrap_or(0);
+        let mut species_per_synonym_count = vec![0; max_per_species + 1];
+        for synonym_count in &synonym_counts {
+            species_per_synonym_count[*synonym_count] += 1;
+        }
+        let total_synonyms: usize = synonym_counts.iter().sum();
+
+        let mut largest_genera = rank_counts(moth_data, |x| Some(&x.classification.genus));
+        largest_genera.truncate(top_genera);
+
+        return MothStats {
+            species: moth_data.len(),
+            with_common_names: species_share(|x| x.common_names.is_some()),
+            with_distribution: species_share(|x| x.distribution.is_some()),
+            with_threat_status: species_share(|x| {
+                x.distribution
+                    .as_ref()
+                    .is_some_and(|x| x.threat_status.is_some())
+            }),
+            extinct: species_share(|x| {
+                x.species_profile
+                    .as_ref()
+                    .is_some_and(|x| x.extinct == Some(true))
+            }),
+            marine: species_share(|x| {
+                x.species_profile
+                    .as_ref()
+                    .is_some_and(|x| x.marine == Some(true))
+            }),
+            freshwater: species_share(|x| {
+                x.species_profile
+                    .as_ref()
+                    .is_some_and(|x| x.freshwater == Some(true))
+            }),
+            terrestrial: species_share(|x| {
+                x.species_profile
+                    .as_ref()
+                    .is_some_and(|x| x.terrestrial == Some(true))
+            }),
+            synonyms: SynonymStats {
+                total: total_synonyms,
+                species_with_synonyms: species_share(|x| x.synonyms.is_some()),
+                mean_per_species: if moth_data.is_empty() {
+                    0.0
+                } else {
+                    total_synonyms as f64 / moth_data.len() as f64
+                },
+                max_per_species,
+                species_per_synonym_count,
+            },
+            superfamilies: rank_counts(moth_data, |x| x.classification.superfamily.as_ref()),
+            families: rank_counts(moth_data, |x| x.classification.family.as_ref()),
+            subfamilies: rank_counts(moth_data, |x| x.classification.subfamily.as_ref()),
+            largest_genera,
+        };
+    }
+
+    /// Markdown tables for release notes.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Moth Data Statistics\n\n");
+        markdown += "| | Species | Share |\n| --- | ---: | ---: |\n";
+        markdown += &format!("| Total | {} | |\n", self.species);
+        for (label, species_share) in [
+            ("With common names", &self.with_common_names),
+            ("With distribution", &self.with_distribution),
+            ("With threat status", &self.with_threat_status),
+            ("With synonyms", &self.synonyms.species_with_synonyms),
+            ("Extinct", &self.extinct),
+            ("Marine", &self.marine),
+            ("Freshwater", &self.freshwater),
+            ("Terrestrial", &self.terrestrial),
+        ] {
+            markdown += &format!(
+                "| {label} | {} | {:.1}% |\n",
+                species_share.species,
+                species_share.share * 100.0
+            );
+        }
+
+        markdown += "\n## Synonyms\n\n| | |\n| --- | ---: |\n";
+        markdown += &format!("| Total | {} |\n", self.synonyms.total);
+        markdown += &format!(
+            "| Mean per species | {:.2} |\n",
+            self.synonyms.mean_per_species
+        );
+        markdown += &format!("| Max per species | {} |\n", self.synonyms.max_per_species);
+        markdown += "\n| Synonyms | Species |\n| ---: | ---: |\n";
+        for (synonym_count, species) in self.synonyms.species_per_synonym_count.iter().enumerate() {
+            if *species > 0 {
+                markdown += &format!("| {synonym_count} | {species} |\n");
+            }
+        }
+
+        for (heading, rank, rank_counts) in [
+            ("Superfamilies", "Superfamily", &self.superfamilies),
+            ("Families", "Family", &self.families),
+            ("Subfamilies", "Subfamily", &self.subfamilies),
+            ("Largest Genera", "Genus", &self.largest_genera),
+        ] {
+            markdown += &format!("\n## {heading}\n\n| {rank} | Species |\n| --- | ---: |\n");
+            for rank_count in rank_counts {
+                markdown += &format!("| {} | {} |\n", rank_count.name, rank_count.species);
+            }
+        }
+        return markdown;
+    }
+}
+
+fn share(species: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    return species as f64 / total as f64;
+}
+
+// largest first, ties by name so the output is stable
+fn rank_counts(
+    moth_data: &[SpeciesData],
+    rank_name: fn(&SpeciesData) -> Option<&String>,
+) -> Vec<RankCount> {
+    let mut species_per_rank_name: HashMap<&str, usize> = HashMap::new();
+    for species in moth_data {
+        let name = rank_name(species).map_or(UNASSIGNED_RANK_NAME, |x| x.as_str());
+        *species_per_rank_name.entry(name).or_default() += 1;
+    }
+    let mut rank_counts: Vec<RankCount> = species_per_rank_name
+        .into_iter()
+        .map(|(name, species)| RankCount {
+            name: name.to_string(),
+            species,
+        })
+        .collect();
+    rank_counts.sort_by(|a, b| b.species.cmp(&a.species).then_with(|| a.name.cmp(&b.name)));
+    return rank_counts;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{species, synonym};
+
+    fn moth_data() -> Vec<SpeciesData> {
+        let mut moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("2", "Catocala", "sponsa"),
+            species("3", "Catocala", "fraxini"),
+            species("4", "Agrotis", "ipsilon"),
+            species("5", "Agrotis", "segetum"),
+            species("6", "Cydia", "pomonella"),
+        ];
+        for species in &mut moth_data[..3] {
+            species.classification.superfamily = Some("Noctuoidea".to_string());
+            species.classification.family = Some("Erebidae".to_string());
+        }
+        for species in &mut moth_data[3..5] {
+            species.classification.superfamily = Some("Noctuoidea".to_string());
+            species.classification.family = Some("Noctuidae".to_string());
+        }
+        moth_data[0].synonyms = Some(vec![synonym("S1", "Phalaena", "nupta")]);
+        moth_data[3].synonyms = Some(vec![
+            synonym("S2", "Feltia", "ipsilon"),
+            synonym("S3", "Agrotis", "suffusa"),
+            synonym("S4", "Noctua", "ipsilon"),
+        ]);
+        moth_data[5].common_names = Some(vec!["Codling Moth".to_string()]);
+        return moth_data;
+    }
+
+    fn rank_count_pairs(rank_counts: &[RankCount]) -> Vec<(&str, usize)> {
+        return rank_counts
+            .iter()
+            .map(|x| (x.name.as_str(), x.species))
+            .collect();
+    }
+
+    #[test]
+    fn counts_species_per_rank() {
+        let moth_stats = MothStats::new(&moth_data(), DEFAULT_TOP_GENERA);
+        assert_eq!(moth_stats.species, 6);
+        assert_eq!(
+            rank_count_pairs(&moth_stats.superfamilies),
+            [("Noctuoidea", 5), (UNASSIGNED_RANK_NAME, 1)]
+        );
+        // ties are ordered by name
+        assert_eq!(
+            rank_count_pairs(&moth_stats.families),
+            [("Erebidae", 3), ("Noctuidae", 2), (UNASSIGNED_RANK_NAME, 1)]
+        );
+        assert_eq!(
+            rank_count_pairs(&moth_stats.subfamilies),
+            [(UNASSIGNED_RANK_NAME, 6)]
+        );
+        assert_eq!(
+            rank_count_pairs(&moth_stats.largest_genera),
+            [("Catocala", 3), ("Agrotis", 2), ("Cydia", 1)]
+        );
+        assert_eq!(moth_stats.with_common_names.species, 1);
+        assert!((moth_stats.with_common_names.share - 1.0 / 6.0).abs() < 1e-9);
+
+        let moth_stats = MothStats::new(&moth_data(), 2);
+        assert_eq!(
+            rank_count_pairs(&moth_stats.largest_genera),
+            [("Catocala", 3), ("Agrotis", 2)]
+        );
+    }
+
+    #[test]
+    fn counts_synonyms_per_species() {
+        let moth_stats = MothStats::new(&moth_data(), DEFAULT_TOP_GENERA);
+        assert_eq!(moth_stats.synonyms.total, 4);
+        assert_eq!(moth_stats.synonyms.species_with_synonyms.species, 2);
+        assert_eq!(moth_stats.synonyms.max_per_species, 3);
+        assert!((moth_stats.synonyms.mean_per_species - 4.0 / 6.0).abs() < 1e-9);
+        // 4 species without synonyms, 1 with one and 1 with three
+        assert_eq!(moth_stats.synonyms.species_per_synonym_count, [4, 1, 0, 1]);
+
+        let markdown = moth_stats.to_markdown();
+        assert!(markdown.contains(
+            "| Synonyms | Species |\n| ---: | ---: |\n| 0 | 4 |\n| 1 | 1 |\n| 3 | 1 |\n"
+        ));
+    }
+
+    #[test]
+    fn handles_empty_moth_data() {
+        let moth_stats = MothStats::new(&[], DEFAULT_TOP_GENERA);
+        assert_eq!(moth_stats.species, 0);
+        assert_eq!(moth_stats.synonyms.mean_per_species, 0.0);
+        assert_eq!(moth_stats.synonyms.species_per_synonym_count, [0]);
+        assert_eq!(moth_stats.with_common_names.share, 0.0);
+        assert!(moth_stats.families.is_empty());
+    }
+}
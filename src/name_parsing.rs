@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+
+const AUTHOR_PARTICLES: [&str; 8] = ["de", "d'", "von", "van", "der", "du", "la", "le"];
+const INFRASPECIFIC_RANK_MARKERS: [&str; 6] = ["subsp.", "ssp.", "var.", "f.", "form.", "ab."];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameQualifier {
+    /// `cf.`, compare with
+    Confer,
+    /// `aff.`, has affinity with
+    Affinis,
+    /// `nr.`, near
+    Near,
+    /// `sp.`, unidentified species
+    Species,
+    /// `spp.`, several unidentified species
+    SpeciesPlural,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParsedName {
+    pub genus: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subgenus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epithet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infraspecific_epithet: Option<String>,
+    // without the surrounding parentheses, see `parenthesized_author`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorship: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+    // the species was originally described in a different genus
+    pub parenthesized_author: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub qualifiers: Vec<NameQualifier>,
+}
+
+impl ParsedName {
+    /// `Genus epithet` as used for keys in `MothSynonyms`, `None` for names that are not resolved to a species.
+    pub fn species_key(&self) -> Option<String> {
+        if self
+            .qualifiers
+            .iter()
+            .any(|x| matches!(x, NameQualifier::Species | NameQualifier::SpeciesPlural))
+        {
+            return None;
+        }
+        let epithet = self.epithet.as_ref()?;
+        return Some(format!("{} {}", self.genus, epithet));
+    }
+}
+
+/// Splits a free form name such as `Agrotis ipsilon (Hufnagel, 1766)` or `Catocala sp.` into its parts.
+pub fn parse_scientific_name(name: &str) -> Option<ParsedName> {
+    let tokens: Vec<&str> = name.split_whitespace().collect();
+    let (genus, mut remaining) = tokens.split_first()?;
+    let genus = genus.trim_matches(|x: char| !x.is_alphabetic());
+    let mut genus_chars = genus.chars();
+    let genus_first_char = genus_chars.next()?;
+    // field data often has the genus in lowercase
+    let genus_capitalized: String = genus_first_char
+        .to_uppercase()
+        .chain(genus_chars.flat_map(|x| x.to_lowercase()))
+        .collect();
+    let mut parsed_name = ParsedName {
+        genus: genus_capitalized,
+        ..Default::default()
+    };
+
+    // `Agrotis (Agrotis) ipsilon` has a subgenus, `Agrotis (Hufnagel)` a parenthesized author
+    if let Some((token, rest)) = remaining.split_first()
+        && let Some(inner) = token.strip_prefix('(').and_then(|x| x.strip_suffix(')'))
+        && inner.starts_with(|x: char| x.is_uppercase())
+        && inner.chars().all(|x| x.is_alphabetic())
+        && rest.first().is_some_and(|x| {
+            x.starts_with(|x: char| x.is_lowercase())
+                || parse_qualifier(&x.to_lowercase()).is_some()
+        })
+    {
+        parsed_name.subgenus = Some(inner.to_string());
+        remaining = rest;
+    }
+
+    while let Some((token, rest)) = remaining.split_first() {
+        let token_lowercase = token.to_lowercase();
+        if let Some(qualifier) = parse_qualifier(&token_lowercase) {
+            parsed_name.qualifiers.push(qualifier);
+            remaining = rest;
+            continue;
+        }
+        // morphospecies numbering such as `sp. 2`
+        let is_species_number = token.chars().all(|x| x.is_ascii_digit())
+            && parsed_name.qualifiers.last() == Some(&NameQualifier::Species);
+        if INFRASPECIFIC_RANK_MARKERS.contains(&token_lowercase.as_str()) || is_species_number {
+            remaining = rest;
+            continue;
+        }
+        // particles can be chained as in `van der Wulp`
+        let is_author_particle = AUTHOR_PARTICLES.contains(&token_lowercase.as_str())
+            && rest.first().is_some_and(|x| {
+                x.starts_with(|x: char| x.is_uppercase())
+                    || AUTHOR_PARTICLES.contains(&x.to_lowercase().as_str())
+            });
+        let is_epithet = token.starts_with(|x: char| x.is_lowercase())
+            && token.chars().all(|x| x.is_alphabetic() || x == '-')
+            && !is_author_particle;
+        if !is_epithet {
+            break;
+        }
+        if parsed_name.epithet.is_none() {
+            parsed_name.epithet = Some(token.to_string());
+        } else if parsed_name.infraspecific_epithet.is_none() {
+            parsed_name.infraspecific_epithet = Some(token.to_string());
+        } else {
+            break;
+        }
+        remaining = rest;
+    }
+
+    if !remaining.is_empty() {
+        let mut authorship = remaining.join(" ");
+        if let Some(inner) = authorship
+            .strip_prefix('(')
+            .and_then(|x| x.strip_suffix(')'))
+            && !inner.contains(['(', ')'])
+        {
+            parsed_name.parenthesized_author = true;
+            authorship = inner.to_string();
+        }
+        parsed_name.year = parse_year(&authorship);
+        parsed_name.authorship = Some(authorship.trim().to_string());
+    }
+
+    return Some(parsed_name);
+}
+
+fn parse_qualifier(token: &str) -> Option<NameQualifier> {
+    return match token.trim_end_matches('.') {
+        "cf" => Some(NameQualifier::Confer),
+        "aff" => Some(NameQualifier::Affinis),
+        "nr" | "near" => Some(NameQualifier::Near),
+        "sp" => Some(NameQualifier::Species),
+        "spp" => Some(NameQualifier::SpeciesPlural),
+        _ => None,
+    };
+}
+
+// the last four digit number in the authorship, e.g. `1766` in `Hufnagel, 1766`
+fn parse_year(authorship: &str) -> Option<u16> {
+    return authorship
+        .split(|x: char| !x.is_ascii_digit())
+        .filter(|x| x.len() == 4)
+        .filter_map(|x| x.parse::<u16>().ok())
+        .next_back();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hand-written `Taxon.tsv` rows in the layout of a COL release, not an actual export
+    const TAXON_TSV: &str = include_str!("../tests/fixtures/Taxon.tsv");
+
+    fn parse(name: &str) -> ParsedName {
+        return parse_scientific_name(name).unwrap();
+    }
+
+    #[test]
+    fn parses_species_with_parenthesized_author() {
+        let parsed_name = parse("Agrotis ipsilon (Hufnagel, 1766)");
+        assert_eq!(parsed_name.genus, "Agrotis");
+        assert_eq!(parsed_name.subgenus, None);
+        assert_eq!(parsed_name.epithet.as_deref(), Some("ipsilon"));
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Hufnagel, 1766"));
+        assert_eq!(parsed_name.year, Some(1766));
+        assert!(parsed_name.parenthesized_author);
+        assert_eq!(
+            parsed_name.species_key().as_deref(),
+            Some("Agrotis ipsilon")
+        );
+    }
+
+    #[test]
+    fn parses_bracketed_year() {
+        // brackets mark a year that was inferred rather than printed in the publication
+        let parsed_name = parse("Helicoverpa armigera (Hübner, [1808])");
+        assert_eq!(parsed_name.epithet.as_deref(), Some("armigera"));
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Hübner, [1808]"));
+        assert_eq!(parsed_name.year, Some(1808));
+        assert!(parsed_name.parenthesized_author);
+
+        let parsed_name = parse("Helicoverpa armigera Hübner, [1808]");
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Hübner, [1808]"));
+        assert_eq!(parsed_name.year, Some(1808));
+        assert!(!parsed_name.parenthesized_author);
+    }
+
+    #[test]
+    fn parses_unidentified_species() {
+        let parsed_name = parse("Catocala sp.");
+        assert_eq!(parsed_name.genus, "Catocala");
+        assert_eq!(parsed_name.epithet, None);
+        assert_eq!(parsed_name.qualifiers, vec![NameQualifier::Species]);
+        assert_eq!(parsed_name.species_key(), None);
+
+        let parsed_name = parse("Catocala sp. 2");
+        assert_eq!(parsed_name.qualifiers, vec![NameQualifier::Species]);
+        assert_eq!(parsed_name.authorship, None);
+
+        assert_eq!(
+            parse("Catocala spp.").qualifiers,
+            vec![NameQualifier::SpeciesPlural]
+        );
+    }
+
+    #[test]
+    fn parses_qualifiers() {
+        let parsed_name = parse("Catocala cf. nupta");
+        assert_eq!(parsed_name.qualifiers, vec![NameQualifier::Confer]);
+        assert_eq!(parsed_name.species_key().as_deref(), Some("Catocala nupta"));
+
+        let parsed_name = parse("Catocala aff. nupta Linnaeus");
+        assert_eq!(parsed_name.qualifiers, vec![NameQualifier::Affinis]);
+        assert_eq!(parsed_name.epithet.as_deref(), Some("nupta"));
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Linnaeus"));
+    }
+
+    #[test]
+    fn parses_subgenus() {
+        let parsed_name = parse("Catocala (Catocala) nupta (Linnaeus, 1767)");
+        assert_eq!(parsed_name.subgenus.as_deref(), Some("Catocala"));
+        assert_eq!(parsed_name.epithet.as_deref(), Some("nupta"));
+        assert_eq!(parsed_name.year, Some(1767));
+
+        let parsed_name = parse("Catocala (Catocala) sp.");
+        assert_eq!(parsed_name.subgenus.as_deref(), Some("Catocala"));
+        assert_eq!(parsed_name.qualifiers, vec![NameQualifier::Species]);
+    }
+
+    #[test]
+    fn parses_parenthesized_author_after_genus() {
+        let parsed_name = parse("Agrotis (Hufnagel)");
+        assert_eq!(parsed_name.subgenus, None);
+        assert_eq!(parsed_name.epithet, None);
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Hufnagel"));
+        assert!(parsed_name.parenthesized_author);
+    }
+
+    #[test]
+    fn parses_infraspecific_epithet() {
+        let parsed_name = parse("Zygaena filipendulae stephensi Dupont, 1900");
+        assert_eq!(parsed_name.epithet.as_deref(), Some("filipendulae"));
+        assert_eq!(
+            parsed_name.infraspecific_epithet.as_deref(),
+            Some("stephensi")
+        );
+        assert_eq!(parsed_name.authorship.as_deref(), Some("Dupont, 1900"));
+        assert!(!parsed_name.parenthesized_author);
+
+        let parsed_name = parse("Zygaena filipendulae ssp. stephensi");
+        assert_eq!(
+            parsed_name.infraspecific_epithet.as_deref(),
+            Some("stephensi")
+        );
+    }
+
+    #[test]
+    fn parses_author_particles() {
+        let parsed_name = parse("Eoophyla conjunctalis de Joannis, 1930");
+        assert_eq!(parsed_name.epithet.as_deref(), Some("conjunctalis"));
+        assert_eq!(parsed_name.infraspecific_epithet, None);
+        assert_eq!(parsed_name.authorship.as_deref(), Some("de Joannis, 1930"));
+
+        let parsed_name = parse("Oligostigma excelsalis van der Wulp, 1881");
+        assert_eq!(parsed_name.infraspecific_epithet, None);
+        assert_eq!(
+            parsed_name.authorship.as_deref(),
+            Some("van der Wulp, 1881")
+        );
+    }
+
+    #[test]
+    fn normalizes_genus_case() {
+        assert_eq!(parse("agrotis ipsilon").genus, "Agrotis");
+        assert_eq!(parse("AGROTIS ipsilon").genus, "Agrotis");
+        assert_eq!(parse_scientific_name("   "), None);
+    }
+
+    #[test]
+    fn round_trips_release_names() {
+        let mut lines = TAXON_TSV.lines();
+        let headers: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let column = |name: &str| headers.iter().position(|x| *x == name).unwrap();
+        let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+        let mut name_count = 0;
+        for line in lines {
+            let row: Vec<&str> = line.split('\t').collect();
+            let scientific_name = row[column("dwc:scientificName")];
+            let authorship = row[column("dwc:scientificNameAuthorship")];
+            let parsed_name = parse(&format!("{scientific_name} {authorship}"));
+
+            assert_eq!(parsed_name.genus, row[column("dwc:genericName")], "{line}");
+            assert_eq!(
+                parsed_name.subgenus,
+                non_empty(row[column("dwc:infragenericEpithet")]),
+                "{line}"
+            );
+            assert_eq!(
+                parsed_name.epithet,
+                non_empty(row[column("dwc:specificEpithet")]),
+                "{line}"
+            );
+            assert_eq!(
+                parsed_name.infraspecific_epithet,
+                non_empty(row[column("dwc:infraspecificEpithet")]),
+                "{line}"
+            );
+            let parsed_authorship = parsed_name.authorship.unwrap();
+            if parsed_name.parenthesized_author {
+                assert_eq!(format!("({parsed_authorship})"), authorship, "{line}");
+            } else {
+                assert_eq!(parsed_authorship, authorship, "{line}");
+            }
+            name_count += 1;
+        }
+        assert!(name_count > 0);
+    }
+}
@@ -0,0 +1,24 @@
+use std::io::{BufReader, Read, Write};
+
+use crate::SpeciesData;
+
+/// Lazily reads `moth_data.jsonl`, one `SpeciesData` per line.
+pub fn read_moth_data_ndjson<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<SpeciesData, serde_json::Error>> {
+    return serde_json::Deserializer::from_reader(BufReader::new(reader))
+        .into_iter::<SpeciesData>();
+}
+
+/// Writes each species as a single line of json.
+pub fn write_moth_data_ndjson<W: Write>(
+    mut writer: W,
+    moth_data: &[SpeciesData],
+) -> Result<(), serde_json::Error> {
+    for species in moth_data {
+        serde_json::to_writer(&mut writer, species)?;
+        writer.write_all(b"\n").map_err(serde_json::Error::io)?;
+    }
+    writer.flush().map_err(serde_json::Error::io)?;
+    return Ok(());
+}
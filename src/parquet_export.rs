@@ -0,0 +1,225 @@
+use std::{error::Error, fs::File, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, BooleanArray, RecordBatch, StringArray,
+    builder::{ListBuilder, StringBuilder, StructBuilder},
+};
+use arrow_schema::{DataType, Field};
+use moth_filter::*;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+
+/// Species table with one row per `SpeciesData`, common names, country codes, location ids and synonyms as nested lists.
+pub fn write_moth_data_parquet(
+    output_file_path: &str,
+    moth_entries: &[SpeciesData],
+) -> Result<(), Box<dyn Error>> {
+    let string_column = |get: fn(&SpeciesData) -> Option<&str>| -> ArrayRef {
+        return Arc::new(StringArray::from_iter(moth_entries.iter().map(get)));
+    };
+    let bool_column = |get: fn(&SpeciesData) -> Option<bool>| -> ArrayRef {
+        return Arc::new(BooleanArray::from_iter(moth_entries.iter().map(get)));
+    };
+
+    let string_list_column = |get: fn(&SpeciesData) -> Option<&Vec<String>>| -> ArrayRef {
+        let mut list_builder = ListBuilder::new(StringBuilder::new());
+        for species in moth_entries {
+            match get(species) {
+                Some(values) => {
+                    for value in values {
+                        list_builder.values().append_value(value);
+                    }
+                    list_builder.append(true);
+                }
+                None => list_builder.append(false),
+            }
+        }
+        return Arc::new(list_builder.finish());
+    };
+
+    let synonym_fields = vec![
+        Field::new("catalogue_of_life_taxon_id", DataType::Utf8, false),
+        Field::new("genus", DataType::Utf8, false),
+        Field::new("epithet", DataType::Utf8, false),
+    ];
+    let mut synonyms_builder = ListBuilder::new(StructBuilder::from_fields(synonym_fields, 0));
+    for species in moth_entries {
+        let Some(synonyms) = &species.synonyms else {
+            synonyms_builder.append(false);
+            continue;
+        };
+        let synonym_builder = synonyms_builder.values();
+        for synonym in synonyms {
+            for (field_index, value) in [
+                &synonym.catalogue_of_life_taxon_id,
+                &synonym.genus,
+                &synonym.epithet,
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                synonym_builder
+                    .field_builder::<StringBuilder>(field_index)
+                    .ok_or("synonym field builder type mismatch")?
+                    .append_value(value);
+            }
+            synonym_builder.append(true);
+        }
+        synonyms_builder.append(true);
+    }
+
+    let species_batch = RecordBatch::try_from_iter(vec![
+        (
+            "catalogue_of_life_taxon_id",
+            string_column(|x| Some(&x.catalogue_of_life_taxon_id)),
+        ),
+        (
+            "superfamily",
+            string_column(|x| x.classification.superfamily.as_deref()),
+        ),
+        (
+            "family",
+            string_column(|x| x.classification.family.as_deref()),
+        ),
+        (
+            "subfamily",
+            string_column(|x| x.classification.subfamily.as_deref()),
+        ),
+        (
+            "tribe",
+            string_column(|x| x.classification.tribe.as_deref()),
+        ),
+        (
+            "subtribe",
+            string_column(|x| x.classification.subtribe.as_deref()),
+        ),
+        ("genus", string_column(|x| Some(&x.classification.genus))),
+        (
+            "epithet",
+            string_column(|x| Some(&x.classification.epithet)),
+        ),
+        (
+            "generic_name",
+            string_column(|x| x.classification.generic_name.as_deref()),
+        ),
+        (
+            "common_names",
+            string_list_column(|x| x.common_names.as_ref()),
+        ),
+        (
+            "extinct",
+            bool_column(|x| x.species_profile.as_ref().and_then(|x| x.extinct)),
+        ),
+        (
+            "freshwater",
+            bool_column(|x| x.species_profile.as_ref().and_then(|x| x.freshwater)),
+        ),
+        (
+            "marine",
+            bool_column(|x| x.species_profile.as_ref().and_then(|x| x.marine)),
+        ),
+        (
+            "terrestrial",
+            bool_column(|x| x.species_profile.as_ref().and_then(|x| x.terrestrial)),
+        ),
+        (
+            "habitat",
+            Arc::new(StringArray::from_iter(moth_entries.iter().map(|x| {
+                x.species_profile
+                    .as_ref()
+                    .and_then(|x| x.habitat)
+                    .map(|x| format!("{x:?}"))
+            }))) as ArrayRef,
+        ),
+        (
+            "locality",
+            string_column(|x| x.distribution.as_ref().and_then(|x| x.locality.as_deref())),
+        ),
+        (
+            "localities",
+            string_list_column(|x| x.distribution.as_ref().and_then(|x| x.localities.as_ref())),
+        ),
+        (
+            "country_codes",
+            string_list_column(|x| {
+                x.distribution
+                    .as_ref()
+                    .and_then(|x| x.country_codes.as_ref())
+            }),
+        ),
+        (
+            "location_ids",
+            string_list_column(|x| {
+                x.distribution
+                    .as_ref()
+                    .and_then(|x| x.location_ids.as_ref())
+            }),
+        ),
+        (
+            "threat_status",
+            Arc::new(StringArray::from_iter(moth_entries.iter().map(|x| {
+                x.distribution
+                    .as_ref()
+                    .and_then(|x| x.threat_status.as_ref())
+                    .map(|x| format!("{x:?}"))
+            }))) as ArrayRef,
+        ),
+        (
+            "threat_status_source",
+            string_column(|x| x.distribution.as_ref().and_then(|x| x.source.as_deref())),
+        ),
+        ("synonyms", Arc::new(synonyms_builder.finish()) as ArrayRef),
+        ("published_in", string_column(|x| x.published_in.as_deref())),
+    ])?;
+
+    return write_parquet(output_file_path, &species_batch);
+}
+
+/// Synonym table with one row per `SynonymSpecies` and the accepted taxon id it belongs to.
+pub fn write_moth_synonyms_parquet(
+    output_file_path: &str,
+    moth_entries: &[SpeciesData],
+) -> Result<(), Box<dyn Error>> {
+    let synonym_rows: Vec<(&SynonymSpecies, &str)> = moth_entries
+        .iter()
+        .flat_map(|species| {
+            species
+                .synonyms
+                .iter()
+                .flatten()
+                .map(|x| (x, species.catalogue_of_life_taxon_id.as_str()))
+        })
+        .collect();
+    let string_column = |get: fn(&(&SynonymSpecies, &str)) -> String| -> ArrayRef {
+        return Arc::new(StringArray::from_iter_values(synonym_rows.iter().map(get)));
+    };
+
+    let synonyms_batch = RecordBatch::try_from_iter(vec![
+        (
+            "catalogue_of_life_taxon_id",
+            string_column(|x| x.0.catalogue_of_life_taxon_id.clone()),
+        ),
+        ("genus", string_column(|x| x.0.genus.clone())),
+        ("epithet", string_column(|x| x.0.epithet.clone())),
+        ("accepted_taxon_id", string_column(|x| x.1.to_string())),
+    ])?;
+
+    return write_parquet(output_file_path, &synonyms_batch);
+}
+
+fn write_parquet(output_file_path: &str, record_batch: &RecordBatch) -> Result<(), Box<dyn Error>> {
+    let writer_properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(
+        File::create(output_file_path)?,
+        record_batch.schema(),
+        Some(writer_properties),
+    )?;
+    writer.write(record_batch)?;
+    writer.close()?;
+    return Ok(());
+}
@@ -0,0 +1,239 @@
+use std::{error::Error, path::Path};
+
+use moth_filter::*;
+
+const NAME_COLUMN_CANDIDATES: [&str; 5] = [
+    "scientificname",
+    "scientific_name",
+    "dwc:scientificname",
+    "name",
+    "species",
+];
+
+#[derive(Default)]
+pub struct ReconcileCounts {
+    pub accepted: usize,
+    pub synonym: usize,
+    pub fuzzy: usize,
+    pub no_match: usize,
+}
+
+/// Appends the resolved accepted taxon to every row of a CSV or TSV of names.
+pub fn reconcile_file(
+    input_path: &str,
+    output_path: &str,
+    name_column: Option<&str>,
+    moth_lookup: &MothLookup,
+    fuzzy_min_score: Option<f64>,
+) -> Result<ReconcileCounts, Box<dyn Error>> {
+    let delimiter = delimiter_for_path(input_path);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(input_path)?;
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(output_path)?;
+
+    let headers = reader.headers()?.clone();
+    let name_column_index = match name_column {
+        Some(name_column) => headers
+            .iter()
+            .position(|x| x == name_column)
+            .ok_or(format!("Column `{name_column}` not found in {input_path}"))?,
+        None => NAME_COLUMN_CANDIDATES
+            .iter()
+            .find_map(|candidate| {
+                headers
+                    .iter()
+                    .position(|x| x.trim().to_lowercase() == *candidate)
+            })
+            .ok_or(format!(
+                "No name column found in {input_path}, pick one with `--column`"
+            ))?,
+    };
+
+    let mut output_headers = headers.clone();
+    output_headers.push_field("catalogue_of_life_taxon_id");
+    output_headers.push_field("accepted_name");
+    output_headers.push_field("match_type");
+    output_headers.push_field("confidence");
+    output_headers.push_field("lepidoptera_group");
+    writer.write_record(&output_headers)?;
+
+    let mut counts: ReconcileCounts = Default::default();
+    for record_result in reader.records() {
+        // rows with a different field count are padded or cut to the header so the result columns line up
+        let mut record: csv::StringRecord = record_result?
+            .iter()
+            .chain(std::iter::repeat(""))
+            .take(headers.len())
+            .collect();
+        let name = record
+            .get(name_column_index)
+            .unwrap_or_default()
+            .to_string();
+        let resolution = moth_lookup.resolve_name(&name, fuzzy_min_score);
+        match resolution.match_type {
+            MatchType::Accepted => counts.accepted += 1,
+            MatchType::Synonym => counts.synonym += 1,
+            MatchType::Fuzzy => counts.fuzzy += 1,
+            MatchType::NoMatch => counts.no_match += 1,
+        }
+        record.push_field(
+            resolution
+                .catalogue_of_life_taxon_id
+                .as_deref()
+                .unwrap_or_default(),
+        );
+        record.push_field(resolution.accepted_name.as_deref().unwrap_or_default());
+        record.push_field(&resolution.match_type.to_string());
+        record.push_field(&format!("{:.3}", resolution.confidence));
+        record.push_field(&resolution.group.to_string());
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+
+    return Ok(counts);
+}
+
+pub fn default_output_path(input_path: &str) -> String {
+    let path = Path::new(input_path);
+    let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let file_name = if extension.is_empty() {
+        format!("{file_stem}_reconciled")
+    } else {
+        format!("{file_stem}_reconciled.{extension}")
+    };
+    return path.with_file_name(file_name).to_string_lossy().to_string();
+}
+
+fn delimiter_for_path(path: &str) -> u8 {
+    if path.to_lowercase().ends_with(".tsv") {
+        return b'\t';
+    }
+    return b',';
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moth_lookup() -> MothLookup {
+        let moth_data: MothDataJson = serde_json::from_str(
+            r#"[
+                {"catalogue_of_life_taxon_id": "1", "classification": {"genus": "Catocala", "epithet": "nupta"}},
+                {"catalogue_of_life_taxon_id": "2", "classification": {"genus": "Actias", "epithet": "luna"}}
+            ]"#,
+        )
+        .unwrap();
+        let moth_synonyms = MothSynonyms::from([("Phalaena nupta".to_string(), "1".to_string())]);
+        return MothLookup::new(moth_data, moth_synonyms, Default::default());
+    }
+
+    // reconciles `input` written to a temporary file and returns the output file contents
+    fn reconcile(
+        file_name: &str,
+        input: &str,
+        name_column: Option<&str>,
+    ) -> Result<(String, ReconcileCounts), Box<dyn Error>> {
+        let input_path =
+            std::env::temp_dir().join(format!("moth_filter_{}_{file_name}", std::process::id()));
+        let input_path = input_path.to_string_lossy().to_string();
+        let output_path = default_output_path(&input_path);
+        std::fs::write(&input_path, input)?;
+        let counts = reconcile_file(
+            &input_path,
+            &output_path,
+            name_column,
+            &moth_lookup(),
+            Some(DEFAULT_FUZZY_MIN_SCORE),
+        );
+        let output = std::fs::read_to_string(&output_path);
+        let _ = std::fs::remove_file(&input_path);
+        let _ = std::fs::remove_file(&output_path);
+        return Ok((output?, counts?));
+    }
+
+    #[test]
+    fn picks_name_column_from_header() {
+        let (output, counts) = reconcile(
+            "header.csv",
+            "id,scientificName\n7,Catocala nupta\n8,Phalaena nupta\n9,Actias lunna\n10,Vanessa cardui\n",
+            None,
+        )
+        .unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "id,scientificName,catalogue_of_life_taxon_id,accepted_name,match_type,confidence,lepidoptera_group"
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some("7,Catocala nupta,1,Catocala nupta,accepted,1.000,moth")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("8,Phalaena nupta,1,Catocala nupta,synonym,1.000,moth")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("9,Actias lunna,2,Actias luna,fuzzy,0.909,moth")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("10,Vanessa cardui,,,no_match,0.000,neither")
+        );
+        assert_eq!(
+            (
+                counts.accepted,
+                counts.synonym,
+                counts.fuzzy,
+                counts.no_match
+            ),
+            (1, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn uses_given_name_column() {
+        let (output, _) = reconcile(
+            "column.tsv",
+            "taxon\tid\nCatocala cf. nupta\t7\n",
+            Some("taxon"),
+        )
+        .unwrap();
+        assert_eq!(
+            output.lines().nth(1),
+            Some("Catocala cf. nupta\t7\t1\tCatocala nupta\taccepted\t0.500\tmoth")
+        );
+        assert!(reconcile("missing.tsv", "taxon\tid\n", Some("name")).is_err());
+    }
+
+    #[test]
+    fn rejects_file_without_name_column() {
+        let err = reconcile("no_header.csv", "id,taxon\n7,Catocala nupta\n", None)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("--column"));
+    }
+
+    #[test]
+    fn pads_and_cuts_ragged_rows() {
+        let (output, _) = reconcile(
+            "ragged.csv",
+            "name,note\nCatocala nupta\nActias luna,a,b\n",
+            None,
+        )
+        .unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[1],
+            "Catocala nupta,,1,Catocala nupta,accepted,1.000,moth"
+        );
+        assert_eq!(lines[2], "Actias luna,a,2,Actias luna,accepted,1.000,moth");
+    }
+}
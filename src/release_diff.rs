@@ -0,0 +1,579 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use serde::Serialize;
+
+use crate::{ButterflyBlacklist, ScientificClassification, SpeciesData, species_name};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffSpecies {
+    pub catalogue_of_life_taxon_id: String,
+    pub name: String,
+}
+
+/// The same species name under a different taxon id.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxonIdChange {
+    pub name: String,
+    pub old_catalogue_of_life_taxon_id: String,
+    pub new_catalogue_of_life_taxon_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankChange {
+    pub rank: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reclassification {
+    pub catalogue_of_life_taxon_id: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub rank_changes: Vec<RankChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SynonymChange {
+    pub catalogue_of_life_taxon_id: String,
+    pub accepted_name: String,
+    pub synonym_catalogue_of_life_taxon_id: String,
+    pub synonym_name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommonNameChange {
+    pub catalogue_of_life_taxon_id: String,
+    pub name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlacklistChange {
+    pub rank: &'static str,
+    pub name: String,
+}
+
+/// Everything that changed between two generated releases of the moth data.
+#[derive(Debug, Default, Serialize)]
+pub struct ReleaseDiff {
+    pub added_species: Vec<DiffSpecies>,
+    pub removed_species: Vec<DiffSpecies>,
+    pub taxon_id_changes: Vec<TaxonIdChange>,
+    pub reclassifications: Vec<Reclassification>,
+    pub added_synonyms: Vec<SynonymChange>,
+    pub removed_synonyms: Vec<SynonymChange>,
+    pub common_name_changes: Vec<CommonNameChange>,
+    pub added_blacklist_names: Vec<BlacklistChange>,
+    pub removed_blacklist_names: Vec<BlacklistChange>,
+}
+
+impl ReleaseDiff {
+    pub fn is_empty(&self) -> bool {
+        return self.added_species.is_empty()
+            && self.removed_species.is_empty()
+            && self.taxon_id_changes.is_empty()
+            && self.reclassifications.is_empty()
+            && self.added_synonyms.is_empty()
+            && self.removed_synonyms.is_empty()
+            && self.common_name_changes.is_empty()
+            && self.added_blacklist_names.is_empty()
+            && self.removed_blacklist_names.is_empty();
+    }
+}
+
+/// Compares two releases. Species are paired by taxon id, species whose id disappeared are paired by name with new ids.
+/// Blacklist changes are only reported when both blacklists are given.
+pub fn diff_releases(
+    old_moth_data: &[SpeciesData],
+    new_moth_data: &[SpeciesData],
+    old_butterfly_blacklist: Option<&ButterflyBlacklist>,
+    new_butterfly_blacklist: Option<&ButterflyBlacklist>,
+) -> ReleaseDiff {
+    let mut release_diff: ReleaseDiff = Default::default();
+
+    let new_by_id: HashMap<&str, &SpeciesData> = new_moth_data
+        .iter()
+        .map(|x| (x.catalogue_of_life_taxon_id.as_str(), x))
+        .collect();
+    let old_ids: HashSet<&str> = old_moth_data
+        .iter()
+        .map(|x| x.catalogue_of_life_taxon_id.as_str())
+        .collect();
+    // new species without an old id, the candidates for id changes
+    let mut new_by_name_with_new_id: HashMap<String, &SpeciesData> = new_moth_data
+        .iter()
+        .filter(|x| !old_ids.contains(x.catalogue_of_life_taxon_id.as_str()))
+        .map(|x| (species_name(x), x))
+        .collect();
+
+    let mut species_pairs: Vec<(&SpeciesData, &SpeciesData)> = Vec::new();
+    let mut paired_new_ids: HashSet<&str> = HashSet::new();
+    for old_species in old_moth_data {
+        if let Some(new_species) = new_by_id.get(old_species.catalogue_of_life_taxon_id.as_str()) {
+            species_pairs.push((old_species, new_species));
+            paired_new_ids.insert(&new_species.catalogue_of_life_taxon_id);
+            continue;
+        }
+        let old_name = species_name(old_species);
+        if let Some(new_species) = new_by_name_with_new_id.remove(&old_name) {
+            paired_new_ids.insert(&new_species.catalogue_of_life_taxon_id);
+            release_diff.taxon_id_changes.push(TaxonIdChange {
+                name: old_name,
+                old_catalogue_of_life_taxon_id: old_species.catalogue_of_life_taxon_id.clone(),
+                new_catalogue_of_life_taxon_id: new_species.catalogue_of_life_taxon_id.clone(),
+            });
+            species_pairs.push((old_species, new_species));
+            continue;
+        }
+        release_diff.removed_species.push(DiffSpecies {
+            catalogue_of_life_taxon_id: old_species.catalogue_of_life_taxon_id.clone(),
+            name: old_name,
+        });
+    }
+    for new_species in new_moth_data {
+        if !paired_new_ids.contains(new_species.catalogue_of_life_taxon_id.as_str()) {
+            release_diff.added_species.push(DiffSpecies {
+                catalogue_of_life_taxon_id: new_species.catalogue_of_life_taxon_id.clone(),
+                name: species_name(new_species),
+            });
+        }
+    }
+
+    for (old_species, new_species) in species_pairs {
+        let rank_changes = rank_changes(&old_species.classification, &new_species.classification);
+        if !rank_changes.is_empty() {
+            release_diff.reclassifications.push(Reclassification {
+                catalogue_of_life_taxon_id: new_species.catalogue_of_life_taxon_id.clone(),
+                old_name: species_name(old_species),
+                new_name: species_name(new_species),
+                rank_changes,
+            });
+        }
+
+        let synonym_names = |species: &SpeciesData| -> BTreeSet<(String, String)> {
+            return species
+                .synonyms
+                .iter()
+                .flatten()
+                .map(|x| {
+                    (
+                        x.catalogue_of_life_taxon_id.clone(),
+                        format!("{} {}", x.genus, x.epithet),
+                    )
+                })
+                .collect();
+        };
+        let old_synonyms = synonym_names(old_species);
+        let new_synonyms = synonym_names(new_species);
+        let synonym_change = |(synonym_taxon_id, synonym_name): &(String, String)| SynonymChange {
+            catalogue_of_life_taxon_id: new_species.catalogue_of_life_taxon_id.clone(),
+            accepted_name: species_name(new_species),
+            synonym_catalogue_of_life_taxon_id: synonym_taxon_id.clone(),
+            synonym_name: synonym_name.clone(),
+        };
+        release_diff
+            .added_synonyms
+            .extend(new_synonyms.difference(&old_synonyms).map(synonym_change));
+        release_diff
+            .removed_synonyms
+            .extend(old_synonyms.difference(&new_synonyms).map(synonym_change));
+
+        let common_names = |species: &SpeciesData| -> BTreeSet<String> {
+            return species.common_names.iter().flatten().cloned().collect();
+        };
+        let old_common_names = common_names(old_species);
+        let new_common_names = common_names(new_species);
+        if old_common_names != new_common_names {
+            release_diff.common_name_changes.push(CommonNameChange {
+                catalogue_of_life_taxon_id: new_species.catalogue_of_life_taxon_id.clone(),
+                name: species_name(new_species),
+                added: new_common_names
+                    .difference(&old_common_names)
+                    .cloned()
+                    .collect(),
+                removed: old_common_names
+                    .difference(&new_common_names)
+                    .cloned()
+                    .collect(),
+            });
+        }
+    }
+
+    if let Some(old_butterfly_blacklist) = old_butterfly_blacklist
+        && let Some(new_butterfly_blacklist) = new_butterfly_blacklist
+    {
+        for ((rank, old_names), (_, new_names)) in blacklist_ranks(old_butterfly_blacklist)
+            .into_iter()
+            .zip(blacklist_ranks(new_butterfly_blacklist))
+        {
+            // listed alphabetically within each rank
+            let old_names: BTreeSet<&String> = old_names.iter().collect();
+            let new_names: BTreeSet<&String> = new_names.iter().collect();
+            release_diff
+                .added_blacklist_names
+                .extend(new_names.difference(&old_names).map(|x| BlacklistChange {
+                    rank,
+                    name: x.to_string(),
+                }));
+            release_diff
+                .removed_blacklist_names
+                .extend(old_names.difference(&new_names).map(|x| BlacklistChange {
+                    rank,
+                    name: x.to_string(),
+                }));
+        }
+    }
+
+    return release_diff;
+}
+
+fn rank_changes(
+    old_classification: &ScientificClassification,
+    new_classification: &ScientificClassification,
+) -> Vec<RankChange> {
+    let ranks = |x: &ScientificClassification| -> [(&'static str, Option<String>); 7] {
+        return [
+            ("superfamily", x.superfamily.clone()),
+            ("family", x.family.clone()),
+            ("subfamily", x.subfamily.clone()),
+            ("tribe", x.tribe.clone()),
+            ("subtribe", x.subtribe.clone()),
+            ("genus", Some(x.genus.clone())),
+            ("epithet", Some(x.epithet.clone())),
+        ];
+    };
+    return ranks(old_classification)
+        .into_iter()
+        .zip(ranks(new_classification))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((rank, old), (_, new))| RankChange { rank, old, new })
+        .collect();
+}
+
+fn blacklist_ranks(
+    butterfly_blacklist: &ButterflyBlacklist,
+) -> [(&'static str, &HashSet<String>); 6] {
+    return [
+        ("family", &butterfly_blacklist.families),
+        ("subfamily", &butterfly_blacklist.subfamilies),
+        ("tribe", &butterfly_blacklist.tribes),
+        ("subtribe", &butterfly_blacklist.subtribes),
+        ("genus", &butterfly_blacklist.genera),
+        ("epithet", &butterfly_blacklist.epithets),
+    ];
+}
+
+impl Display for ReleaseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        if !self.added_species.is_empty() {
+            writeln!(f, "Added species ({}):", self.added_species.len())?;
+            for species in &self.added_species {
+                writeln!(
+                    f,
+                    "  + {} ({})",
+                    species.name, species.catalogue_of_life_taxon_id
+                )?;
+            }
+        }
+        if !self.removed_species.is_empty() {
+            writeln!(f, "Removed species ({}):", self.removed_species.len())?;
+            for species in &self.removed_species {
+                writeln!(
+                    f,
+                    "  - {} ({})",
+                    species.name, species.catalogue_of_life_taxon_id
+                )?;
+            }
+        }
+        if !self.taxon_id_changes.is_empty() {
+            writeln!(f, "Taxon id changes ({}):", self.taxon_id_changes.len())?;
+            for change in &self.taxon_id_changes {
+                writeln!(
+                    f,
+                    "  {}: {} -> {}",
+                    change.name,
+                    change.old_catalogue_of_life_taxon_id,
+                    change.new_catalogue_of_life_taxon_id
+                )?;
+            }
+        }
+        if !self.reclassifications.is_empty() {
+            writeln!(f, "Reclassified ({}):", self.reclassifications.len())?;
+            for reclassification in &self.reclassifications {
+                let rank_changes: Vec<String> = reclassification
+                    .rank_changes
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "{} {} -> {}",
+                            x.rank,
+                            x.old.as_deref().unwrap_or("(none)"),
+                            x.new.as_deref().unwrap_or("(none)")
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    f,
+                    "  {} ({}): {}",
+                    reclassification.old_name,
+                    reclassification.catalogue_of_life_taxon_id,
+                    rank_changes.join(", ")
+                )?;
+            }
+        }
+        for (heading, sign, synonym_changes) in [
+            ("Added synonyms", '+', &self.added_synonyms),
+            ("Removed synonyms", '-', &self.removed_synonyms),
+        ] {
+            if synonym_changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "{heading} ({}):", synonym_changes.len())?;
+            for change in synonym_changes {
+                writeln!(
+                    f,
+                    "  {sign} {} ({}) of {} ({})",
+                    change.synonym_name,
+                    change.synonym_catalogue_of_life_taxon_id,
+                    change.accepted_name,
+                    change.catalogue_of_life_taxon_id
+                )?;
+            }
+        }
+        if !self.common_name_changes.is_empty() {
+            writeln!(
+                f,
+                "Common name changes ({}):",
+                self.common_name_changes.len()
+            )?;
+            for change in &self.common_name_changes {
+                writeln!(
+                    f,
+                    "  {} ({}):",
+                    change.name, change.catalogue_of_life_taxon_id
+                )?;
+                for common_name in &change.added {
+                    writeln!(f, "    + {common_name}")?;
+                }
+                for common_name in &change.removed {
+                    writeln!(f, "    - {common_name}")?;
+                }
+            }
+        }
+        for (heading, sign, blacklist_changes) in [
+            (
+                "Added butterfly blacklist names",
+                '+',
+                &self.added_blacklist_names,
+            ),
+            (
+                "Removed butterfly blacklist names",
+                '-',
+                &self.removed_blacklist_names,
+            ),
+        ] {
+            if blacklist_changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "{heading} ({}):", blacklist_changes.len())?;
+            for change in blacklist_changes {
+                writeln!(f, "  {sign} {} {}", change.rank, change.name)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{species, synonym};
+
+    fn taxon_ids(diff_species: &[DiffSpecies]) -> Vec<&str> {
+        return diff_species
+            .iter()
+            .map(|x| x.catalogue_of_life_taxon_id.as_str())
+            .collect();
+    }
+
+    #[test]
+    fn reports_nothing_for_identical_releases() {
+        let moth_data = vec![species("1", "Catocala", "nupta")];
+        assert!(diff_releases(&moth_data, &moth_data, None, None).is_empty());
+    }
+
+    #[test]
+    fn reports_added_and_removed_species() {
+        let old_moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("2", "Catocala", "sponsa"),
+        ];
+        let new_moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("3", "Actias", "luna"),
+        ];
+        let release_diff = diff_releases(&old_moth_data, &new_moth_data, None, None);
+        assert_eq!(taxon_ids(&release_diff.removed_species), ["2"]);
+        assert_eq!(release_diff.removed_species[0].name, "Catocala sponsa");
+        assert_eq!(taxon_ids(&release_diff.added_species), ["3"]);
+        assert!(release_diff.taxon_id_changes.is_empty());
+        assert!(release_diff.reclassifications.is_empty());
+    }
+
+    #[test]
+    fn pairs_renumbered_species_by_name() {
+        let old_moth_data = vec![species("1", "Catocala", "nupta")];
+        let mut renumbered = species("9", "Catocala", "nupta");
+        renumbered.common_names = Some(vec!["Red Underwing".to_string()]);
+        let new_moth_data = vec![renumbered];
+        let release_diff = diff_releases(&old_moth_data, &new_moth_data, None, None);
+        assert!(release_diff.added_species.is_empty());
+        assert!(release_diff.removed_species.is_empty());
+        assert_eq!(release_diff.taxon_id_changes.len(), 1);
+        let taxon_id_change = &release_diff.taxon_id_changes[0];
+        assert_eq!(taxon_id_change.name, "Catocala nupta");
+        assert_eq!(taxon_id_change.old_catalogue_of_life_taxon_id, "1");
+        assert_eq!(taxon_id_change.new_catalogue_of_life_taxon_id, "9");
+        // the pair is compared like any other
+        assert_eq!(release_diff.common_name_changes.len(), 1);
+        assert_eq!(release_diff.common_name_changes[0].added, ["Red Underwing"]);
+    }
+
+    #[test]
+    fn prefers_taxon_id_over_name() {
+        // the old id still exists under a new name, so the species was renamed and not renumbered
+        let old_moth_data = vec![species("1", "Phalaena", "nupta")];
+        let new_moth_data = vec![
+            species("1", "Catocala", "nupta"),
+            species("2", "Phalaena", "nupta"),
+        ];
+        let release_diff = diff_releases(&old_moth_data, &new_moth_data, None, None);
+        assert!(release_diff.taxon_id_changes.is_empty());
+        assert_eq!(taxon_ids(&release_diff.added_species), ["2"]);
+        assert_eq!(release_diff.reclassifications.len(), 1);
+        let reclassification = &release_diff.reclassifications[0];
+        assert_eq!(reclassification.catalogue_of_life_taxon_id, "1");
+        assert_eq!(reclassification.old_name, "Phalaena nupta");
+        assert_eq!(reclassification.new_name, "Catocala nupta");
+    }
+
+    #[test]
+    fn reports_rank_changes() {
+        let mut old_species = species("1", "Catocala", "nupta");
+        old_species.classification.family = Some("Noctuidae".to_string());
+        let mut new_species = species("1", "Catocala", "nupta");
+        new_species.classification.family = Some("Erebidae".to_string());
+        new_species.classification.subfamily = Some("Erebinae".to_string());
+        let release_diff = diff_releases(&[old_species], &[new_species], None, None);
+        assert_eq!(release_diff.reclassifications.len(), 1);
+        let rank_changes: Vec<(&str, Option<&str>, Option<&str>)> = release_diff.reclassifications
+            [0]
+        .rank_changes
+        .iter()
+        .map(|x| (x.rank, x.old.as_deref(), x.new.as_deref()))
+        .collect();
+        assert_eq!(
+            rank_changes,
+            [
+                ("family", Some("Noctuidae"), Some("Erebidae")),
+                ("subfamily", None, Some("Erebinae"))
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_synonym_changes() {
+        let mut old_species = species("1", "Agrotis", "ipsilon");
+        old_species.synonyms = Some(vec![
+            synonym("S1", "Feltia", "ipsilon"),
+            synonym("S2", "Agrotis", "suffusa"),
+        ]);
+        let mut new_species = species("1", "Agrotis", "ipsilon");
+        new_species.synonyms = Some(vec![
+            synonym("S1", "Feltia", "ipsilon"),
+            synonym("S3", "Noctua", "ipsilon"),
+        ]);
+        let release_diff = diff_releases(&[old_species], &[new_species], None, None);
+        let synonym_changes = |synonym_changes: &[SynonymChange]| -> Vec<(String, String)> {
+            return synonym_changes
+                .iter()
+                .map(|x| {
+                    (
+                        x.synonym_catalogue_of_life_taxon_id.clone(),
+                        x.synonym_name.clone(),
+                    )
+                })
+                .collect();
+        };
+        assert_eq!(
+            synonym_changes(&release_diff.added_synonyms),
+            [("S3".to_string(), "Noctua ipsilon".to_string())]
+        );
+        assert_eq!(
+            synonym_changes(&release_diff.removed_synonyms),
+            [("S2".to_string(), "Agrotis suffusa".to_string())]
+        );
+        assert_eq!(
+            release_diff.added_synonyms[0].accepted_name,
+            "Agrotis ipsilon"
+        );
+        assert!(release_diff.reclassifications.is_empty());
+    }
+
+    #[test]
+    fn reports_blacklist_changes_only_with_both_blacklists() {
+        let moth_data = vec![species("1", "Catocala", "nupta")];
+        let mut old_butterfly_blacklist: ButterflyBlacklist = Default::default();
+        old_butterfly_blacklist.genera.insert("vanessa".to_string());
+        old_butterfly_blacklist
+            .genera
+            .insert("catocala".to_string());
+        let mut new_butterfly_blacklist: ButterflyBlacklist = Default::default();
+        new_butterfly_blacklist.genera.insert("vanessa".to_string());
+        new_butterfly_blacklist.genera.insert("pieris".to_string());
+        new_butterfly_blacklist.genera.insert("papilio".to_string());
+        new_butterfly_blacklist
+            .families
+            .insert("pieridae".to_string());
+
+        let release_diff = diff_releases(
+            &moth_data,
+            &moth_data,
+            Some(&old_butterfly_blacklist),
+            Some(&new_butterfly_blacklist),
+        );
+        let blacklist_changes = |blacklist_changes: &[BlacklistChange]| -> Vec<(&str, String)> {
+            return blacklist_changes
+                .iter()
+                .map(|x| (x.rank, x.name.clone()))
+                .collect();
+        };
+        assert_eq!(
+            blacklist_changes(&release_diff.added_blacklist_names),
+            [
+                ("family", "pieridae".to_string()),
+                ("genus", "papilio".to_string()),
+                ("genus", "pieris".to_string())
+            ]
+        );
+        assert_eq!(
+            blacklist_changes(&release_diff.removed_blacklist_names),
+            [("genus", "catocala".to_string())]
+        );
+
+        assert!(
+            diff_releases(&moth_data, &moth_data, Some(&old_butterfly_blacklist), None).is_empty()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::{BufRead, Write};
+
+use moth_filter::*;
+
+const FIND_LIMIT: usize = 50;
+const MATCH_LIMIT: usize = 10;
+const REPL_HELP: &str = "\
+Commands:
+  find <text>                 Species whose name, classification or common names contain text
+  id <taxonID>                Species by Catalogue of Life taxon id
+  name <Genus epithet>        Species by accepted name
+  syn <Genus epithet>         Accepted species for a synonym
+  match <name>                Fuzzy match a possibly misspelled name
+  family <Family> [--count]   Species in a family
+  classify <rank>=<name> ...  Moth or butterfly by family, subfamily, tribe, subtribe, genus or epithet
+  help                        Show this help
+  quit                        Exit";
+
+/// Reads commands from stdin until `quit` or end of input.
+pub fn run_repl(moth_lookup: &MothLookup) {
+    println!(
+        "Loaded {} moths and {} synonyms, type `help` for commands",
+        moth_lookup.moth_data.len(),
+        moth_lookup.moth_synonyms.len()
+    );
+    let stdin = std::io::stdin();
+    let mut input_lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let Some(Ok(input_line)) = input_lines.next() else {
+            break;
+        };
+        let input_line = input_line.trim();
+        let (command, argument) = input_line.split_once(' ').unwrap_or((input_line, ""));
+        let argument = argument.trim().trim_matches('"');
+        match command {
+            "" => (),
+            "find" => print_species_list(&moth_lookup.search(argument, FIND_LIMIT)),
+            "id" => match moth_lookup.species_by_id(argument) {
+                Some(species) => println!("{}", format_species(species)),
+                None => println!("No moth with taxon id `{argument}`"),
+            },
+            "name" => match moth_lookup.species_by_name(argument) {
+                Some(species) => println!("{}", format_species(species)),
+                None => println!("No accepted moth named `{argument}`"),
+            },
+            "syn" => match moth_lookup
+                .moth_synonyms
+                .get(argument)
+                .and_then(|x| moth_lookup.species_by_id(x))
+            {
+                Some(species) => println!("{}", format_species(species)),
+                None => println!("No moth synonym named `{argument}`"),
+            },
+            "match" => {
+                let matches = moth_lookup.fuzzy_matcher().find_matches(
+                    argument,
+                    DEFAULT_FUZZY_MIN_SCORE,
+                    MATCH_LIMIT,
+                );
+                if matches.is_empty() {
+                    println!("No matches found for `{argument}`");
+                }
+                for fuzzy_match in matches {
+                    println!(
+                        "{:.3}  {} -> {} ({})",
+                        fuzzy_match.score,
+                        fuzzy_match.matched_name,
+                        fuzzy_match.accepted_name,
+                        fuzzy_match.catalogue_of_life_taxon_id
+                    );
+                }
+            }
+            "family" => {
+                let count_only = argument.ends_with("--count");
+                let family = argument.trim_end_matches("--count").trim();
+                let family_species: Vec<&SpeciesData> = moth_lookup
+                    .moth_data
+                    .iter()
+                    .filter(|x| {
+                        x.classification
+                            .family
+                            .as_ref()
+                            .is_some_and(|x| x.eq_ignore_ascii_case(family))
+                    })
+                    .collect();
+                if count_only {
+                    println!("{} species in {}", family_species.len(), family);
+                } else {
+                    print_species_list(&family_species);
+                }
+            }
+            "classify" => {
+                let mut classification_query: ClassificationQuery = Default::default();
+                for rank_argument in argument.split_whitespace() {
+                    let Some((rank, name)) = rank_argument.split_once('=') else {
+                        println!("Expected <rank>=<name>, got `{rank_argument}`");
+                        continue;
+                    };
+                    let name = Some(name.trim_matches('"').to_string());
+                    match rank {
+                        "family" => classification_query.family = name,
+                        "subfamily" => classification_query.subfamily = name,
+                        "tribe" => classification_query.tribe = name,
+                        "subtribe" => classification_query.subtribe = name,
+                        "genus" => classification_query.genus = name,
+                        "epithet" => classification_query.epithet = name,
+                        _ => println!("Unknown rank `{rank}`"),
+                    }
+                }
+                println!("{}", moth_lookup.classify(&classification_query));
+            }
+            "help" => println!("{REPL_HELP}"),
+            "quit" | "exit" => break,
+            _ => println!("Unknown command `{command}`, type `help` for commands"),
+        }
+    }
+}
+
+fn print_species_list(species_list: &[&SpeciesData]) {
+    if species_list.is_empty() {
+        println!("No species found");
+    }
+    for species in species_list {
+        println!(
+            "{}  {}  ({})",
+            species.catalogue_of_life_taxon_id,
+            species_name(species),
+            species
+                .classification
+                .family
+                .as_deref()
+                .unwrap_or("unknown family")
+        );
+    }
+}
+
+fn format_species(species: &SpeciesData) -> String {
+    let classification = &species.classification;
+    let mut lines = vec![format!(
+        "{} ({})",
+        species_name(species),
+        species.catalogue_of_life_taxon_id
+    )];
+    for (rank, name) in [
+        ("Superfamily", &classification.superfamily),
+        ("Family", &classification.family),
+        ("Subfamily", &classification.subfamily),
+        ("Tribe", &classification.tribe),
+        ("Subtribe", &classification.subtribe),
+    ] {
+        if let Some(name) = name {
+            lines.push(format!("  {rank}: {name}"));
+        }
+    }
+    if let Some(generic_name) = &classification.generic_name {
+        lines.push(format!(
+            "  Published as: {} {} (genus disagrees)",
+            generic_name, classification.epithet
+        ));
+    }
+    if let Some(common_names) = &species.common_names {
+        lines.push(format!("  Common names: {}", common_names.join(", ")));
+    }
+    if let Some(species_profile) = &species.species_profile {
+        let flags: Vec<String> = [
+            ("extinct", species_profile.extinct),
+            ("freshwater", species_profile.freshwater),
+            ("marine", species_profile.marine),
+            ("terrestrial", species_profile.terrestrial),
+        ]
+        .into_iter()
+        .filter_map(|(flag, value)| value.map(|x| format!("{flag}: {}", yes_no(x))))
+        .collect();
+        if !flags.is_empty() {
+            lines.push(format!("  Profile: {}", flags.join(", ")));
+        }
+        if let Some(habitat) = &species_profile.habitat {
+            lines.push(format!("  Habitat: {habitat:?}"));
+        }
+    }
+    if let Some(distribution) = &species.distribution {
+        if let Some(localities) = &distribution.localities {
+            lines.push(format!("  Localities: {}", localities.join("; ")));
+        } else if let Some(locality) = &distribution.locality {
+            lines.push(format!("  Locality: {locality}"));
+        }
+        if let Some(country_codes) = &distribution.country_codes {
+            lines.push(format!("  Countries: {}", country_codes.join(", ")));
+        }
+        if let Some(threat_status) = &distribution.threat_status {
+            lines.push(format!("  Threat status: {threat_status:?}"));
+        }
+        if let Some(source) = &distribution.source {
+            lines.push(format!("  Source: {source}"));
+        }
+    }
+    if let Some(synonyms) = &species.synonyms {
+        let synonym_names: Vec<String> = synonyms
+            .iter()
+            .map(|x| {
+                format!(
+                    "{} {} ({})",
+                    x.genus, x.epithet, x.catalogue_of_life_taxon_id
+                )
+            })
+            .collect();
+        lines.push(format!("  Synonyms: {}", synonym_names.join(", ")));
+    }
+    if let Some(published_in) = &species.published_in {
+        lines.push(format!("  Published in: {published_in}"));
+    }
+    return lines.join("\n");
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        return "yes";
+    }
+    return "no";
+}
@@ -0,0 +1,282 @@
+use std::{collections::HashMap, error::Error};
+
+use moth_filter::*;
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct SynonymResponse<'a> {
+    synonym: String,
+    catalogue_of_life_taxon_id: &'a str,
+    species: &'a SpeciesData,
+}
+
+#[derive(Serialize)]
+struct ClassifyResponse {
+    group: LepidopteraGroup,
+}
+
+/// Serves lookups on `moth_lookup` until the process is killed.
+pub fn serve(address: &str, moth_lookup: &MothLookup) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(address)?;
+    println!("Listening on http://{}", server.server_addr());
+    handle_requests(&server, moth_lookup, None);
+    return Ok(());
+}
+
+// stops after `request_limit` requests when given
+fn handle_requests(server: &Server, moth_lookup: &MothLookup, request_limit: Option<usize>) {
+    for request in server
+        .incoming_requests()
+        .take(request_limit.unwrap_or(usize::MAX))
+    {
+        if let Err(err) = handle_request(request, moth_lookup) {
+            eprintln!("Failed to respond: {err}");
+        }
+    }
+}
+
+fn handle_request(request: Request, moth_lookup: &MothLookup) -> std::io::Result<()> {
+    if *request.method() != Method::Get {
+        return request.respond(json_error(405, "Only GET is supported"));
+    }
+    let (path, query_string) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let query = parse_query_string(query_string);
+    // split before decoding so an encoded `/` (`%2F`) stays inside its segment
+    let decoded_path_segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+    let path_segments: Vec<&str> = decoded_path_segments.iter().map(String::as_str).collect();
+
+    let response = match path_segments.as_slice() {
+        ["taxon", taxon_id] => match moth_lookup.species_by_id(taxon_id) {
+            Some(species) => json_response(200, species),
+            None => json_error(404, &format!("No moth with taxon id `{taxon_id}`")),
+        },
+        ["name", name] => match moth_lookup.species_by_name(name) {
+            Some(species) => json_response(200, species),
+            None => json_error(404, &format!("No accepted moth named `{name}`")),
+        },
+        ["synonym", name] => match moth_lookup
+            .moth_synonyms
+            .get(*name)
+            .and_then(|x| moth_lookup.species_by_id(x))
+        {
+            Some(species) => json_response(
+                200,
+                &SynonymResponse {
+                    synonym: name.to_string(),
+                    catalogue_of_life_taxon_id: &species.catalogue_of_life_taxon_id,
+                    species,
+                },
+            ),
+            None => json_error(404, &format!("No moth synonym named `{name}`")),
+        },
+        ["resolve"] => match query.get("name") {
+            Some(name) => {
+                let fuzzy_min_score = if query.get("fuzzy").is_some_and(|x| x == "false") {
+                    None
+                } else {
+                    Some(
+                        query
+                            .get("min_score")
+                            .and_then(|x| x.parse::<f64>().ok())
+                            .unwrap_or(DEFAULT_FUZZY_MIN_SCORE),
+                    )
+                };
+                json_response(200, &moth_lookup.resolve_name(name, fuzzy_min_score))
+            }
+            None => json_error(400, "Missing `name` query parameter"),
+        },
+        ["classify"] => {
+            let classification_query = ClassificationQuery {
+                family: query.get("family").cloned(),
+                subfamily: query.get("subfamily").cloned(),
+                tribe: query.get("tribe").cloned(),
+                subtribe: query.get("subtribe").cloned(),
+                genus: query.get("genus").cloned(),
+                epithet: query.get("epithet").cloned(),
+            };
+            json_response(
+                200,
+                &ClassifyResponse {
+                    group: moth_lookup.classify(&classification_query),
+                },
+            )
+        }
+        ["search"] => match query.get("q") {
+            Some(search_query) => {
+                let limit = query
+                    .get("limit")
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .unwrap_or(DEFAULT_SEARCH_LIMIT);
+                json_response(200, &moth_lookup.search(search_query, limit))
+            }
+            None => json_error(400, "Missing `q` query parameter"),
+        },
+        _ => json_error(404, &format!("Unknown endpoint `{}`", percent_decode(path))),
+    };
+    return request.respond(response);
+}
+
+fn json_response<T: Serialize + ?Sized>(
+    status_code: u16,
+    body: &T,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    let json_body = serde_json::to_vec(body).unwrap_or_default();
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    return Response::from_data(json_body)
+        .with_status_code(status_code)
+        .with_header(content_type);
+}
+
+fn json_error(status_code: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    return json_response(
+        status_code,
+        &ErrorResponse {
+            error: message.to_string(),
+        },
+    );
+}
+
+fn parse_query_string(query_string: &str) -> HashMap<String, String> {
+    return query_string
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (key, value) = x.split_once('=').unwrap_or((x, ""));
+            (
+                percent_decode(&key.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect();
+}
+
+fn percent_decode(input: &str) -> String {
+    let input_bytes = input.as_bytes();
+    let mut decoded_bytes: Vec<u8> = Vec::with_capacity(input_bytes.len());
+    let mut i = 0;
+    while i < input_bytes.len() {
+        if input_bytes[i] == b'%'
+            && let Some(hex) = input.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded_bytes.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded_bytes.push(input_bytes[i]);
+        i += 1;
+    }
+    return String::from_utf8_lossy(&decoded_bytes).to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::*;
+
+    const MOTH_DATA_JSON: &str = r#"[
+        {
+            "catalogue_of_life_taxon_id": "6XQTZ",
+            "classification": {"family": "Noctuidae", "genus": "Agrotis", "epithet": "ipsilon"},
+            "synonyms": [{"catalogue_of_life_taxon_id": "P4L6S", "genus": "Feltia", "epithet": "ipsilon"}]
+        },
+        {
+            "catalogue_of_life_taxon_id": "A/1",
+            "classification": {"family": "Erebidae", "genus": "Catocala", "epithet": "nupta"}
+        }
+    ]"#;
+
+    fn moth_lookup() -> MothLookup {
+        let moth_data: MothDataJson = serde_json::from_str(MOTH_DATA_JSON).unwrap();
+        let mut moth_synonyms: MothSynonyms = Default::default();
+        moth_synonyms.insert("Feltia ipsilon".to_string(), "6XQTZ".to_string());
+        moth_synonyms.insert("Catocala a/b".to_string(), "A/1".to_string());
+        return MothLookup::new(moth_data, moth_synonyms, Default::default());
+    }
+
+    // status code and body of a GET on a fresh connection
+    fn get(address: &str, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status_code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        return (status_code, serde_json::from_str(body).unwrap());
+    }
+
+    #[test]
+    fn serves_lookups() {
+        let moth_lookup = moth_lookup();
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap().to_string();
+        let paths = [
+            "/taxon/6XQTZ",
+            "/taxon/A%2F1",
+            "/name/Agrotis%20ipsilon",
+            "/synonym/Catocala%20a%2Fb",
+            "/resolve?name=Feltia+ipsilon",
+            "/resolve",
+            "/taxon/missing",
+            "/unknown",
+            "/taxon%2F6XQTZ",
+        ];
+        std::thread::scope(|scope| {
+            scope.spawn(|| handle_requests(&server, &moth_lookup, Some(paths.len())));
+
+            let (status_code, body) = get(&address, paths[0]);
+            assert_eq!(status_code, 200);
+            assert_eq!(body["classification"]["genus"], "Agrotis");
+
+            let (status_code, body) = get(&address, paths[1]);
+            assert_eq!(status_code, 200);
+            assert_eq!(body["catalogue_of_life_taxon_id"], "A/1");
+
+            let (status_code, body) = get(&address, paths[2]);
+            assert_eq!(status_code, 200);
+            assert_eq!(body["catalogue_of_life_taxon_id"], "6XQTZ");
+
+            let (status_code, body) = get(&address, paths[3]);
+            assert_eq!(status_code, 200);
+            assert_eq!(body["synonym"], "Catocala a/b");
+            assert_eq!(body["catalogue_of_life_taxon_id"], "A/1");
+
+            let (status_code, body) = get(&address, paths[4]);
+            assert_eq!(status_code, 200);
+            assert_eq!(body["catalogue_of_life_taxon_id"], "6XQTZ");
+            assert_eq!(body["match_type"], "synonym");
+
+            let (status_code, _) = get(&address, paths[5]);
+            assert_eq!(status_code, 400);
+
+            let (status_code, _) = get(&address, paths[6]);
+            assert_eq!(status_code, 404);
+
+            let (status_code, body) = get(&address, paths[7]);
+            assert_eq!(status_code, 404);
+            assert_eq!(body["error"], "Unknown endpoint `/unknown`");
+
+            // an encoded `/` doesn't separate segments
+            let (status_code, _) = get(&address, paths[8]);
+            assert_eq!(status_code, 404);
+        });
+    }
+}
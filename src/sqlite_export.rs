@@ -0,0 +1,257 @@
+use std::{error::Error, path::Path};
+
+use moth_filter::*;
+use rusqlite::{Connection, params};
+
+const SCHEMA: &str = "
+CREATE TABLE species (
+    taxon_id TEXT PRIMARY KEY,
+    scientific_name TEXT NOT NULL,
+    extinct INTEGER,
+    freshwater INTEGER,
+    marine INTEGER,
+    terrestrial INTEGER,
+    habitat TEXT,
+    published_in TEXT
+);
+CREATE TABLE classification (
+    taxon_id TEXT PRIMARY KEY REFERENCES species (taxon_id),
+    superfamily TEXT,
+    family TEXT,
+    subfamily TEXT,
+    tribe TEXT,
+    subtribe TEXT,
+    genus TEXT NOT NULL,
+    epithet TEXT NOT NULL,
+    generic_name TEXT
+);
+CREATE TABLE common_names (
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    name TEXT NOT NULL
+);
+CREATE TABLE synonyms (
+    synonym_taxon_id TEXT NOT NULL,
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    scientific_name TEXT NOT NULL,
+    genus TEXT NOT NULL,
+    epithet TEXT NOT NULL
+);
+CREATE TABLE distributions (
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    locality TEXT,
+    threat_status TEXT,
+    source TEXT
+);
+CREATE TABLE localities (
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    locality TEXT NOT NULL
+);
+CREATE TABLE countries (
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    country_code TEXT NOT NULL
+);
+CREATE TABLE locations (
+    taxon_id TEXT NOT NULL REFERENCES species (taxon_id),
+    location_id TEXT NOT NULL
+);
+CREATE TABLE butterfly_blacklist (
+    rank TEXT NOT NULL,
+    name TEXT NOT NULL
+);
+CREATE TABLE collisions (
+    rank TEXT NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE INDEX species_scientific_name ON species (scientific_name);
+CREATE INDEX classification_family ON classification (family);
+CREATE INDEX classification_genus_epithet ON classification (genus, epithet);
+CREATE INDEX common_names_taxon_id ON common_names (taxon_id);
+CREATE INDEX common_names_name ON common_names (name COLLATE NOCASE);
+CREATE INDEX synonyms_synonym_taxon_id ON synonyms (synonym_taxon_id);
+CREATE INDEX synonyms_taxon_id ON synonyms (taxon_id);
+CREATE INDEX synonyms_scientific_name ON synonyms (scientific_name);
+CREATE INDEX distributions_taxon_id ON distributions (taxon_id);
+CREATE INDEX localities_taxon_id ON localities (taxon_id);
+CREATE INDEX countries_country_code ON countries (country_code);
+CREATE INDEX countries_taxon_id ON countries (taxon_id);
+CREATE INDEX locations_location_id ON locations (location_id);
+CREATE INDEX locations_taxon_id ON locations (taxon_id);
+CREATE INDEX butterfly_blacklist_name ON butterfly_blacklist (name, rank);
+CREATE INDEX collisions_name ON collisions (name, rank);
+";
+
+/// Writes a fresh database with normalized tables of the same data as the json outputs.
+/// Only the joined scientific name is kept on `species` for lookups, genus and epithet are in `classification`.
+pub fn write_sqlite(
+    output_file_path: &str,
+    moth_entries: &[SpeciesData],
+    butterfly_blacklist: &ButterflyBlacklist,
+    butterfly_collisions: &ButterflyBlacklist,
+) -> Result<(), Box<dyn Error>> {
+    if Path::new(output_file_path).exists() {
+        std::fs::remove_file(output_file_path)?;
+    }
+    let mut connection = Connection::open(output_file_path)?;
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+    {
+        let mut species_statement =
+            transaction.prepare("INSERT INTO species VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        let mut classification_statement = transaction
+            .prepare("INSERT INTO classification VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?;
+        let mut common_name_statement =
+            transaction.prepare("INSERT INTO common_names VALUES (?1, ?2)")?;
+        let mut synonym_statement =
+            transaction.prepare("INSERT INTO synonyms VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut distribution_statement =
+            transaction.prepare("INSERT INTO distributions VALUES (?1, ?2, ?3, ?4)")?;
+        let mut locality_statement =
+            transaction.prepare("INSERT INTO localities VALUES (?1, ?2)")?;
+        let mut country_statement = transaction.prepare("INSERT INTO countries VALUES (?1, ?2)")?;
+        let mut location_statement =
+            transaction.prepare("INSERT INTO locations VALUES (?1, ?2)")?;
+
+        for species in moth_entries {
+            let taxon_id = &species.catalogue_of_life_taxon_id;
+            let classification = &species.classification;
+            let species_profile = species.species_profile.as_ref();
+            species_statement.execute(params![
+                taxon_id,
+                species_name(species),
+                species_profile.and_then(|x| x.extinct),
+                species_profile.and_then(|x| x.freshwater),
+                species_profile.and_then(|x| x.marine),
+                species_profile.and_then(|x| x.terrestrial),
+                species_profile
+                    .and_then(|x| x.habitat)
+                    .map(|x| format!("{x:?}")),
+                species.published_in,
+            ])?;
+            classification_statement.execute(params![
+                taxon_id,
+                classification.superfamily,
+                classification.family,
+                classification.subfamily,
+                classification.tribe,
+                classification.subtribe,
+                classification.genus,
+                classification.epithet,
+                classification.generic_name,
+            ])?;
+            for common_name in species.common_names.iter().flatten() {
+                common_name_statement.execute(params![taxon_id, common_name])?;
+            }
+            for synonym in species.synonyms.iter().flatten() {
+                synonym_statement.execute(params![
+                    synonym.catalogue_of_life_taxon_id,
+                    taxon_id,
+                    format!("{} {}", synonym.genus, synonym.epithet),
+                    synonym.genus,
+                    synonym.epithet,
+                ])?;
+            }
+            if let Some(distribution) = &species.distribution {
+                distribution_statement.execute(params![
+                    taxon_id,
+                    distribution.locality,
+                    distribution
+                        .threat_status
+                        .as_ref()
+                        .map(|x| format!("{x:?}")),
+                    distribution.source,
+                ])?;
+                for locality in distribution.localities.iter().flatten() {
+                    locality_statement.execute(params![taxon_id, locality])?;
+                }
+                for country_code in distribution.country_codes.iter().flatten() {
+                    country_statement.execute(params![taxon_id, country_code])?;
+                }
+                for location_id in distribution.location_ids.iter().flatten() {
+                    location_statement.execute(params![taxon_id, location_id])?;
+                }
+            }
+        }
+
+        insert_ranks(&transaction, "butterfly_blacklist", butterfly_blacklist)?;
+        insert_ranks(&transaction, "collisions", butterfly_collisions)?;
+    }
+    transaction.commit()?;
+    return Ok(());
+}
+
+fn insert_ranks(
+    transaction: &rusqlite::Transaction,
+    table: &str,
+    rank_names: &ButterflyBlacklist,
+) -> Result<(), rusqlite::Error> {
+    let mut statement = transaction.prepare(&format!("INSERT INTO {table} VALUES (?1, ?2)"))?;
+    for (rank, names) in [
+        ("family", &rank_names.families),
+        ("subfamily", &rank_names.subfamilies),
+        ("tribe", &rank_names.tribes),
+        ("subtribe", &rank_names.subtribes),
+        ("genus", &rank_names.genera),
+        ("epithet", &rank_names.epithets),
+    ] {
+        let mut names: Vec<&String> = names.iter().collect();
+        names.sort();
+        for name in names {
+            statement.execute(params![rank, name])?;
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_species_with_their_classification() {
+        let moth_entries: Vec<SpeciesData> = serde_json::from_str(
+            r#"[
+                {
+                    "catalogue_of_life_taxon_id": "6XQTZ",
+                    "classification": {"family": "Noctuidae", "genus": "Agrotis", "epithet": "ipsilon"},
+                    "common_names": ["Dark Sword-grass"]
+                }
+            ]"#,
+        )
+        .unwrap();
+        let output_file_path = std::env::temp_dir()
+            .join(format!("moth_filter_{}.sqlite", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        write_sqlite(
+            &output_file_path,
+            &moth_entries,
+            &ButterflyBlacklist::default(),
+            &ButterflyBlacklist::default(),
+        )
+        .unwrap();
+        let connection = Connection::open(&output_file_path).unwrap();
+        let row: (String, String, String, String) = connection
+            .query_row(
+                "SELECT species.scientific_name, classification.family, classification.genus, common_names.name
+                FROM species
+                JOIN classification USING (taxon_id)
+                JOIN common_names USING (taxon_id)
+                WHERE classification.genus = 'Agrotis' AND classification.epithet = 'ipsilon'",
+                [],
+                |x| Ok((x.get(0)?, x.get(1)?, x.get(2)?, x.get(3)?)),
+            )
+            .unwrap();
+        drop(connection);
+        std::fs::remove_file(&output_file_path).unwrap();
+        assert_eq!(
+            row,
+            (
+                "Agrotis ipsilon".to_string(),
+                "Noctuidae".to_string(),
+                "Agrotis".to_string(),
+                "Dark Sword-grass".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use moth_filter::*;
+
+use crate::{tsv_parsing, tsv_types::*};
+
+/// Checks that need `Taxon.tsv` columns which aren't kept in the moth data.
+pub fn lint_taxon_rows(data_dir: &str, moth_data: &[SpeciesData]) -> Vec<LintIssue> {
+    let species_by_id: HashMap<&str, &SpeciesData> = moth_data
+        .iter()
+        .map(|x| (x.catalogue_of_life_taxon_id.as_str(), x))
+        .collect();
+
+    let mut lint_issues: Vec<LintIssue> = Vec::new();
+    let mut taxon_tsv_reader = tsv_parsing::tsv_reader(&format!("{data_dir}/Taxon.tsv"));
+    for tsv_reader_result in taxon_tsv_reader.deserialize::<TaxonTSVRaw>() {
+        let Ok(taxon_tsv_data_raw) = tsv_reader_result else {
+            continue;
+        };
+        let Some(species) = species_by_id.get(taxon_tsv_data_raw.dwc_taxon_id.as_str()) else {
+            continue;
+        };
+
+        if taxon_tsv_data_raw.dwc_parent_name_usage_id.is_empty() {
+            lint_issues.push(LintIssue::new(LintKind::NoParent, species, None));
+        }
+    }
+    return lint_issues;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn reports_species_without_parent() {
+        // 6XQTZ has a parent in the fixture, 3J4KL has none and 0ZZZZ isn't in it
+        let moth_data: Vec<SpeciesData> = serde_json::from_str(
+            r#"[
+                {"catalogue_of_life_taxon_id": "6XQTZ", "classification": {"genus": "Agrotis", "epithet": "ipsilon"}},
+                {"catalogue_of_life_taxon_id": "3J4KL", "classification": {"genus": "Catocala", "epithet": "nupta"}},
+                {"catalogue_of_life_taxon_id": "0ZZZZ", "classification": {"genus": "Agrotis", "epithet": "alba"}}
+            ]"#,
+        )
+        .unwrap();
+        let lint_issues = lint_taxon_rows(FIXTURE_DATA_DIR, &moth_data);
+        assert_eq!(lint_issues.len(), 1);
+        assert_eq!(lint_issues[0].kind, LintKind::NoParent);
+        assert_eq!(lint_issues[0].catalogue_of_life_taxon_id, "3J4KL");
+        assert_eq!(lint_issues[0].name, "Catocala nupta");
+
+        assert!(lint_taxon_rows(FIXTURE_DATA_DIR, &moth_data[..1]).is_empty());
+    }
+}
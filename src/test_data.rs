@@ -0,0 +1,32 @@
+// builders for unit tests, every optional field is left empty
+use crate::{ScientificClassification, SpeciesData, SynonymSpecies};
+
+pub fn species(taxon_id: &str, genus: &str, epithet: &str) -> SpeciesData {
+    return SpeciesData {
+        catalogue_of_life_taxon_id: taxon_id.to_string(),
+        classification: ScientificClassification {
+            superfamily: None,
+            family: None,
+            subfamily: None,
+            tribe: None,
+            subtribe: None,
+            genus: genus.to_string(),
+            epithet: epithet.to_string(),
+            generic_name: None,
+        },
+        common_names: None,
+        species_profile: None,
+        distribution: None,
+        synonyms: None,
+        published_in: None,
+        authorship: None,
+    };
+}
+
+pub fn synonym(taxon_id: &str, genus: &str, epithet: &str) -> SynonymSpecies {
+    return SynonymSpecies {
+        catalogue_of_life_taxon_id: taxon_id.to_string(),
+        genus: genus.to_string(),
+        epithet: epithet.to_string(),
+    };
+}
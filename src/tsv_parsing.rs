@@ -1,44 +1,57 @@
-use std::{collections::HashMap, fs::File};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+};
 
 use crate::{
     addin_tsv_hashmaps::{self, VernacularHashKey},
     tsv_types::*,
 };
 
-pub fn parse_tsvs() -> TSVMaps {
-    let mut vernacular_tsv_reader = csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .quoting(false)
-        .from_reader(File::open("./data/VernacularName.tsv").unwrap());
-    let vernacular_tsv = addin_tsv_hashmaps::vernacular_to_hashmap(
-        vernacular_tsv_reader.deserialize::<VernacularNameTSVRaw>(),
-    );
+/// Parses the side tables concurrently, one thread per file.
+/// With `taxon_ids` only rows for those taxa are kept.
+pub fn parse_tsvs(data_dir: &str, taxon_ids: Option<&HashSet<String>>) -> TSVMaps {
+    return std::thread::scope(|scope| {
+        let vernacular_tsv = scope.spawn(|| {
+            let mut vernacular_tsv_reader = tsv_reader(&format!("{data_dir}/VernacularName.tsv"));
+            addin_tsv_hashmaps::vernacular_to_hashmap(
+                vernacular_tsv_reader.deserialize::<VernacularNameTSVRaw>(),
+                taxon_ids,
+            )
+        });
+        let species_profile_tsv = scope.spawn(|| {
+            let mut species_profile_tsv_reader =
+                tsv_reader(&format!("{data_dir}/SpeciesProfile.tsv"));
+            addin_tsv_hashmaps::species_profile_to_hashmap(
+                species_profile_tsv_reader.deserialize::<SpeciesProfileTSVRaw>(),
+                taxon_ids,
+            )
+        });
+        let distribution_tsv = scope.spawn(|| {
+            let mut distribution_tsv_reader = tsv_reader(&format!("{data_dir}/Distribution.tsv"));
+            addin_tsv_hashmaps::distribution_to_hashmap(
+                distribution_tsv_reader.deserialize::<DistributionTSVRaw>(),
+                taxon_ids,
+            )
+        });
 
-    let mut species_profile_tsv_reader = csv::ReaderBuilder::new()
-        .delimiter(b'\t')
-        .quoting(false)
-        .from_reader(File::open("./data/SpeciesProfile.tsv").unwrap());
-    let species_profile_tsv = addin_tsv_hashmaps::species_profile_to_hashmap(
-        species_profile_tsv_reader.deserialize::<SpeciesProfileTSVRaw>(),
-    );
+        TSVMaps {
+            vernacular_name: vernacular_tsv.join().unwrap(),
+            species_profile: species_profile_tsv.join().unwrap(),
+            distribution: distribution_tsv.join().unwrap(),
+        }
+    });
+}
 
-    let mut distribution_tsv_reader = csv::ReaderBuilder::new()
+pub fn tsv_reader(file_path: &str) -> csv::Reader<File> {
+    return csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .quoting(false)
-        .from_reader(File::open("./data/Distribution.tsv").unwrap());
-    let distribution_tsv = addin_tsv_hashmaps::distribution_to_hashmap(
-        distribution_tsv_reader.deserialize::<DistributionTSVRaw>(),
-    );
-
-    return TSVMaps {
-        vernacular_name: vernacular_tsv,
-        species_profile: species_profile_tsv,
-        distribution: distribution_tsv,
-    };
+        .from_reader(File::open(file_path).unwrap());
 }
 
 pub struct TSVMaps {
     pub vernacular_name: HashMap<VernacularHashKey, Vec<String>>,
     pub species_profile: HashMap<String, SpeciesProfileTSVRaw>,
-    pub distribution: HashMap<String, DistributionTSVRaw>,
+    pub distribution: HashMap<String, Vec<DistributionTSVRaw>>,
 }
@@ -73,6 +73,21 @@ pub struct TaxonTSVRaw {
     pub clb_merged: String,
 }
 
+/// The columns of `Taxon.tsv` needed to tell whether a taxon is a moth, for collecting moth ids without the rest of the row.
+#[derive(Debug, Deserialize)]
+pub struct TaxonFilterTSVRaw {
+    #[serde(rename = "dwc:taxonID")]
+    pub dwc_taxon_id: String,
+    #[serde(rename = "dwc:taxonomicStatus")]
+    pub dwc_taxonomic_status: TaxonomicStatusRaw,
+    #[serde(rename = "dwc:taxonRank")]
+    pub dwc_taxon_rank: String,
+    #[serde(rename = "dwc:order")]
+    pub dwc_order: Option<String>,
+    #[serde(rename = "dwc:superfamily")]
+    pub dwc_superfamily: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub enum TaxonomicStatusRaw {
     #[serde(rename = "accepted")]
@@ -139,27 +154,55 @@ pub struct DistributionTSVRaw {
     #[serde(rename = "dwc:countryCode")]
     pub dwc_country_code: String,
     #[serde(rename = "dcterms:source")]
-    pub dcterms_source: String,
+    pub dcterms_source: Option<String>,
     #[serde(rename = "clb:merged")]
     pub clb_merged: String,
 }
 
+// `iucn:threatStatus` is free text in some sources, so it's parsed by hand rather than failing the whole row
 #[derive(Debug, Deserialize)]
+#[serde(from = "String")]
+#[allow(unused)]
 pub enum ThreatStatusRaw {
-    #[serde(rename = "least concern")]
     LeastConcern,
-    #[serde(rename = "vulnerable")]
+    NearThreatened,
     Vulnerable,
-    #[serde(rename = "endangered")]
     Endangered,
-    #[serde(rename = "critically endangered")]
     CriticallyEndangered,
-    #[serde(rename = "extinct in the wild")]
     ExtinctInTheWild,
-    #[serde(rename = "extinct")]
     Extinct,
-    #[serde(rename = "not evaluated")]
     NotEvaluated,
-    #[serde(rename = "data deficient")]
     DataDeficient,
+    // IUCN 2.3 categories, still used for old assessments
+    LowerRiskConservationDependent,
+    LowerRiskNearThreatened,
+    LowerRiskLeastConcern,
+    Unknown(String),
+}
+
+impl From<String> for ThreatStatusRaw {
+    fn from(value: String) -> ThreatStatusRaw {
+        let normalized = value
+            .trim()
+            .to_lowercase()
+            .replace(['_', '-'], " ")
+            .replace(" / ", "/");
+        return match normalized.as_str() {
+            "lc" | "least concern" => ThreatStatusRaw::LeastConcern,
+            "nt" | "near threatened" => ThreatStatusRaw::NearThreatened,
+            "vu" | "vulnerable" => ThreatStatusRaw::Vulnerable,
+            "en" | "endangered" => ThreatStatusRaw::Endangered,
+            "cr" | "critically endangered" => ThreatStatusRaw::CriticallyEndangered,
+            "ew" | "extinct in the wild" => ThreatStatusRaw::ExtinctInTheWild,
+            "ex" | "extinct" => ThreatStatusRaw::Extinct,
+            "ne" | "not evaluated" => ThreatStatusRaw::NotEvaluated,
+            "dd" | "data deficient" => ThreatStatusRaw::DataDeficient,
+            "lr/cd" | "conservation dependent" | "lower risk/conservation dependent" => {
+                ThreatStatusRaw::LowerRiskConservationDependent
+            }
+            "lr/nt" | "lower risk/near threatened" => ThreatStatusRaw::LowerRiskNearThreatened,
+            "lr/lc" | "lower risk/least concern" => ThreatStatusRaw::LowerRiskLeastConcern,
+            _ => ThreatStatusRaw::Unknown(value),
+        };
+    }
 }
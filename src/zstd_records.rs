@@ -0,0 +1,181 @@
+use std::{collections::HashMap, error::Error, fs::File, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::SpeciesData;
+
+// zstd's own default, dictionaries larger than this barely improve small records
+const MAX_DICTIONARY_SIZE: usize = 112_640;
+// zstd recommends about 100 times the dictionary size in samples
+const MAX_DICTIONARY_SAMPLES_SIZE: usize = MAX_DICTIONARY_SIZE * 100;
+
+/// Location of one species' zstd frame in the records file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordIndexEntry {
+    pub catalogue_of_life_taxon_id: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Trains a dictionary on the species json and writes every species as its own zstd frame compressed with it.
+pub fn write_dictionary_compressed_records(
+    dictionary_file_path: &str,
+    records_file_path: &str,
+    index_file_path: &str,
+    moth_data: &[SpeciesData],
+) -> Result<(), Box<dyn Error>> {
+    let records_json = moth_data
+        .iter()
+        .map(serde_json::to_vec)
+        .collect::<Result<Vec<Vec<u8>>, _>>()?;
+
+    let records_json_size: usize = records_json.iter().map(|x| x.len()).sum();
+    let sample_step = records_json_size
+        .div_ceil(MAX_DICTIONARY_SAMPLES_SIZE)
+        .max(1);
+    let samples: Vec<&Vec<u8>> = records_json.iter().step_by(sample_step).collect();
+    let dictionary = zstd::dict::from_samples(&samples, MAX_DICTIONARY_SIZE)?;
+    File::create(dictionary_file_path)?.write_all(&dictionary)?;
+
+    let max_compression_level = *zstd::compression_level_range().end();
+    let mut compressor =
+        zstd::bulk::Compressor::with_dictionary(max_compression_level, &dictionary)?;
+    let mut records_file = std::io::BufWriter::new(File::create(records_file_path)?);
+    let mut index: Vec<RecordIndexEntry> = Vec::with_capacity(moth_data.len());
+    let mut offset = 0;
+    for (species, record_json) in moth_data.iter().zip(records_json.iter()) {
+        let frame = compressor.compress(record_json)?;
+        records_file.write_all(&frame)?;
+        index.push(RecordIndexEntry {
+            catalogue_of_life_taxon_id: species.catalogue_of_life_taxon_id.clone(),
+            offset,
+            length: frame.len() as u64,
+        });
+        offset += frame.len() as u64;
+    }
+    records_file.flush()?;
+
+    let mut index_file = std::io::BufWriter::new(File::create(index_file_path)?);
+    serde_json::to_writer(&mut index_file, &index)?;
+    index_file.flush()?;
+    return Ok(());
+}
+
+/// Random access to single species in the dictionary compressed records without decompressing the rest.
+pub struct DictionaryCompressedMothData {
+    dictionary: Vec<u8>,
+    records: Vec<u8>,
+    index: HashMap<String, RecordIndexEntry>,
+    taxon_ids: Vec<String>,
+}
+
+impl DictionaryCompressedMothData {
+    pub fn open(
+        dictionary_file_path: &str,
+        records_file_path: &str,
+        index_file_path: &str,
+    ) -> Result<DictionaryCompressedMothData, Box<dyn Error>> {
+        let index: Vec<RecordIndexEntry> =
+            serde_json::from_reader(std::io::BufReader::new(File::open(index_file_path)?))?;
+        return Ok(DictionaryCompressedMothData::from_parts(
+            std::fs::read(dictionary_file_path)?,
+            std::fs::read(records_file_path)?,
+            index,
+        ));
+    }
+
+    /// For data that is already in memory, e.g. bundled in an app.
+    pub fn from_parts(
+        dictionary: Vec<u8>,
+        records: Vec<u8>,
+        index: Vec<RecordIndexEntry>,
+    ) -> DictionaryCompressedMothData {
+        let taxon_ids = index
+            .iter()
+            .map(|x| x.catalogue_of_life_taxon_id.clone())
+            .collect();
+        let index = index
+            .into_iter()
+            .map(|x| (x.catalogue_of_life_taxon_id.clone(), x))
+            .collect();
+        return DictionaryCompressedMothData {
+            dictionary,
+            records,
+            index,
+            taxon_ids,
+        };
+    }
+
+    /// Taxon ids in the order they were written.
+    pub fn taxon_ids(&self) -> &[String] {
+        return &self.taxon_ids;
+    }
+
+    pub fn get(&self, taxon_id: &str) -> Result<Option<SpeciesData>, Box<dyn Error>> {
+        let Some(index_entry) = self.index.get(taxon_id) else {
+            return Ok(None);
+        };
+        let frame = self
+            .records
+            .get(index_entry.offset as usize..(index_entry.offset + index_entry.length) as usize)
+            .ok_or("record index points outside of the records file")?;
+        let decoder = zstd::stream::read::Decoder::with_dictionary(frame, &self.dictionary)?;
+        return Ok(Some(serde_json::from_reader(decoder)?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::species;
+
+    #[test]
+    fn reads_back_single_records() {
+        // dictionary training needs a few hundred samples
+        let moth_data: Vec<SpeciesData> = (0..500)
+            .map(|x| {
+                let mut species = species(&format!("T{x}"), "Catocala", &format!("nupta{x}"));
+                species.classification.family = Some("Erebidae".to_string());
+                species.common_names = Some(vec![format!("Underwing {x}")]);
+                species
+            })
+            .collect();
+        let output_path =
+            std::env::temp_dir().join(format!("moth_filter_zstd_records_{}", std::process::id()));
+        let output_path = output_path.to_string_lossy();
+        let dictionary_file_path = format!("{output_path}.dict");
+        let records_file_path = format!("{output_path}.zst");
+        let index_file_path = format!("{output_path}.json");
+        write_dictionary_compressed_records(
+            &dictionary_file_path,
+            &records_file_path,
+            &index_file_path,
+            &moth_data,
+        )
+        .unwrap();
+        let compressed_moth_data = DictionaryCompressedMothData::open(
+            &dictionary_file_path,
+            &records_file_path,
+            &index_file_path,
+        );
+        for file_path in [&dictionary_file_path, &records_file_path, &index_file_path] {
+            std::fs::remove_file(file_path).unwrap();
+        }
+        let compressed_moth_data = compressed_moth_data.unwrap();
+
+        assert_eq!(compressed_moth_data.taxon_ids().len(), moth_data.len());
+        assert_eq!(compressed_moth_data.taxon_ids()[0], "T0");
+        for taxon_id in ["T317", "T0", "T499", "T42"] {
+            let species = compressed_moth_data.get(taxon_id).unwrap().unwrap();
+            let expected = moth_data
+                .iter()
+                .find(|x| x.catalogue_of_life_taxon_id == taxon_id)
+                .unwrap();
+            assert_eq!(
+                serde_json::to_string(&species).unwrap(),
+                serde_json::to_string(expected).unwrap()
+            );
+        }
+        assert!(compressed_moth_data.get("T500").unwrap().is_none());
+    }
+}